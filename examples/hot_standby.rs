@@ -0,0 +1,123 @@
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rpdo::{
+    context::Basic,
+    host::Host,
+    io::{SimpleClient, SimpleServerProcessor},
+    redundancy::{Monitor, Redundancy, Role},
+};
+
+const PRIMARY: &str = "127.0.0.1:3011";
+const STANDBY: &str = "127.0.0.1:3012";
+
+// Serves a host on the address, `online` simulates the host power/network state
+fn serve(host: Host<Basic>, addr: &str, online: Arc<AtomicBool>) {
+    let listener = TcpListener::bind(addr).unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            if !online.load(Ordering::Acquire) {
+                continue;
+            }
+            stream
+                .set_read_timeout(Some(Duration::from_millis(100)))
+                .unwrap();
+            let mut processor = SimpleServerProcessor::new(host.clone(), stream);
+            let online = online.clone();
+            thread::spawn(move || loop {
+                if !online.load(Ordering::Acquire) {
+                    break;
+                }
+                if let Err(rpdo::Error::Io(e)) = processor.process_next() {
+                    if e.kind() != std::io::ErrorKind::WouldBlock
+                        && e.kind() != std::io::ErrorKind::TimedOut
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn connect(addr: &'static str, target: u32) -> rpdo::Result<SimpleClient<TcpStream>> {
+    let stream = TcpStream::connect(addr)?;
+    stream.set_read_timeout(Some(Duration::from_millis(200)))?;
+    Ok(SimpleClient::new(stream, target))
+}
+
+fn spawn_monitor(
+    redundancy: Redundancy,
+    context: Basic,
+    peer: &'static str,
+    peer_id: u32,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        Monitor::new(redundancy, context, move || connect(peer, peer_id))
+            .with_registers(0..10)
+            .with_max_failures(3)
+            .run(Duration::from_millis(100));
+    })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let primary_online = Arc::new(AtomicBool::new(true));
+    // primary host
+    let primary_context = Basic::new(10, 4, false);
+    let primary_redundancy = Redundancy::new(Role::Primary);
+    serve(
+        Host::new(1, primary_context.clone()).with_redundancy(primary_redundancy.clone()),
+        PRIMARY,
+        primary_online.clone(),
+    );
+    spawn_monitor(primary_redundancy.clone(), primary_context, STANDBY, 2);
+    // standby host
+    let standby_context = Basic::new(10, 4, false);
+    let standby_redundancy = Redundancy::new(Role::Standby);
+    serve(
+        Host::new(2, standby_context.clone()).with_redundancy(standby_redundancy.clone()),
+        STANDBY,
+        Arc::new(AtomicBool::new(true)),
+    );
+    spawn_monitor(standby_redundancy.clone(), standby_context, PRIMARY, 1);
+    thread::sleep(Duration::from_millis(200));
+    // write to the primary, the standby replicates the data and rejects direct writes
+    let mut client = connect(PRIMARY, 1)?;
+    client.write_register(0, 0, &42u32.to_le_bytes())?;
+    thread::sleep(Duration::from_millis(300));
+    let mut standby_client = connect(STANDBY, 2)?;
+    println!(
+        "standby status: {:?}, replicated: {:?}, write: {:?}",
+        standby_client.redundancy_status()?,
+        standby_client.read_register(0, 0, 4)?,
+        standby_client.write_register(0, 0, &[0]),
+    );
+    // the primary fails, the standby takes over with a new epoch
+    primary_online.store(false, Ordering::Release);
+    println!("primary is down");
+    thread::sleep(Duration::from_secs(1));
+    println!(
+        "standby status: {:?}, write: {:?}",
+        standby_client.redundancy_status()?,
+        standby_client.write_register(0, 0, &43u32.to_le_bytes()),
+    );
+    // the former primary recovers and demotes itself as the peer epoch is greater
+    primary_online.store(true, Ordering::Release);
+    println!("primary is back");
+    thread::sleep(Duration::from_millis(300));
+    let mut client = connect(PRIMARY, 1)?;
+    println!(
+        "former primary status: {:?}, write: {:?}",
+        client.redundancy_status()?,
+        client.write_register(0, 0, &44u32.to_le_bytes()),
+    );
+    Ok(())
+}
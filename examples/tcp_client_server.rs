@@ -1,5 +1,5 @@
 use binrw::prelude::*;
-use rpdo::comm::Command;
+use rpdo::comm::{Command, Frame};
 use std::{
     net::TcpListener,
     sync::Arc,
@@ -7,58 +7,44 @@ use std::{
     time::{Duration, Instant},
 };
 
-// A custom command handler, optional. Custom commands start from 0x8000.
-struct CommandHandler {}
-
+// Custom commands, optional. Custom command codes start from 0x8000.
 const COMMAND_POKE: u16 = 0x8000;
 const COMMAND_REVERSE: u16 = 0x8001;
+const COMMAND_ADD: u16 = 0x8002;
 
-#[repr(u16)]
-enum CustomCommand {
-    Poke = COMMAND_POKE,
-    Reverse = COMMAND_REVERSE,
-}
-
-impl TryFrom<Command> for CustomCommand {
-    type Error = rpdo::Error;
-    fn try_from(command: Command) -> Result<Self, Self::Error> {
-        match command {
-            Command::Other(COMMAND_POKE) => Ok(Self::Poke),
-            Command::Other(COMMAND_REVERSE) => Ok(Self::Reverse),
-            _ => Err(Self::Error::InvalidCommand),
-        }
-    }
+// A typed request, packed with binrw
+#[binrw]
+#[brw(little)]
+struct AddRequest {
+    a: u32,
+    b: u32,
 }
 
-impl From<CustomCommand> for Command {
-    fn from(command: CustomCommand) -> Self {
-        Command::Other(command as u16)
-    }
+fn poke(_frame: &Frame, data: &[u8]) -> rpdo::Result<Option<Vec<u8>>> {
+    let s = std::str::from_utf8(data).map_err(rpdo::Error::failed)?;
+    println!("Poked: {}", s);
+    Ok(None)
 }
 
-impl rpdo::host::CustomCommandHandler for CommandHandler {
-    fn handle(&self, frame: &rpdo::comm::Frame, data: &[u8]) -> rpdo::Result<Option<Vec<u8>>> {
-        let custom_command = CustomCommand::try_from(frame.command)?;
-        match custom_command {
-            CustomCommand::Poke => {
-                let s = std::str::from_utf8(data).map_err(rpdo::Error::failed)?;
-                println!("Poked: {}", s);
-                Ok(None)
-            }
-            CustomCommand::Reverse => {
-                let mut buf = Vec::with_capacity(data.len());
-                buf.extend_from_slice(data);
-                buf.reverse();
-                Ok(Some(buf))
-            }
-        }
-    }
+fn reverse(_frame: &Frame, data: &[u8]) -> rpdo::Result<Option<Vec<u8>>> {
+    let mut buf = Vec::with_capacity(data.len());
+    buf.extend_from_slice(data);
+    buf.reverse();
+    Ok(Some(buf))
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // events are printed with their connection and frame spans, filtered by RUST_LOG (e.g. debug)
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
     let context = rpdo::context::Basic::new(1000, 0, true);
     let host = rpdo::host::Host::new(1, context.clone())
-        .with_custom_command_handler(Arc::new(CommandHandler {}));
+        .try_with_command(COMMAND_POKE, "poke", Arc::new(poke))?
+        .try_with_command(COMMAND_REVERSE, "reverse", Arc::new(reverse))?
+        .try_with_typed_command(COMMAND_ADD, "add", |_frame, req: AddRequest| {
+            req.a.checked_add(req.b).ok_or(rpdo::Error::Overflow)
+        })?;
     thread::spawn(move || {
         let listener = TcpListener::bind("0.0.0.0:3003").unwrap();
         for stream in listener.incoming() {
@@ -73,8 +59,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut processor =
                 rpdo::io::SimpleServerProcessor::new(host.clone(), stream).with_always_flush(false);
             thread::spawn(move || loop {
-                if let Err(e) = processor.process_next() {
-                    eprintln!("error: {:?}", e);
+                // errors are reported as tracing events
+                if processor.process_next().is_err() {
                     break;
                 }
             });
@@ -107,11 +93,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
         println!("----------------");
         // custom commands
-        client.communicate(CustomCommand::Poke.into(), b"Hello", false)?;
+        if counter == 1 {
+            for command in client.list_commands()? {
+                println!("custom command 0x{:04X}: {}", command.code, command.name);
+            }
+        }
+        client.communicate(Command::Other(COMMAND_POKE), b"Hello", false)?;
         let response = client
-            .communicate(CustomCommand::Reverse.into(), b"dlrow", true)?
+            .communicate(Command::Other(COMMAND_REVERSE), b"dlrow", true)?
             .unwrap();
         println!("reversed: {:?}", std::str::from_utf8(&response).unwrap());
+        let sum: u32 = client.call(COMMAND_ADD, &AddRequest { a: counter, b: 100 })?;
+        println!("added: {}", sum);
         thread::sleep(Duration::from_secs(1));
     }
 }
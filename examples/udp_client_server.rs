@@ -55,6 +55,10 @@ impl rpdo::host::CustomCommandHandler for CommandHandler {
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // events are printed with their connection and frame spans, filtered by RUST_LOG (e.g. debug)
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .init();
     let context = rpdo::context::Basic::new(1000, 0, true);
     let host = rpdo::host::Host::new(1, context.clone())
         .with_custom_command_handler(Arc::new(CommandHandler {}));
@@ -64,10 +68,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .unwrap()
             .with_timeouts(Duration::from_secs(10), Duration::from_secs(5))
             .unwrap();
-        let mut processor = rpdo::io::SimpleServerProcessor::new(host.clone(), stream);
+        let mut processor = rpdo::io::SimpleServerProcessor::new_datagram(host.clone(), stream);
         thread::spawn(move || loop {
-            if let Err(e) = processor.process_next() {
-                eprintln!("error: {:?}", e);
+            // errors are reported as tracing events
+            if processor.process_next().is_err() {
                 break;
             }
         });
@@ -1,11 +1,21 @@
 use std::io::{Cursor, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use binrw::prelude::*;
 
 use crate::error::Error;
 
 /// The current version of the protocol
-pub const VERSION: u8 = 0x00;
+///
+/// Version 1 adds the negotiation and the extended commands, payload compression and structured
+/// error details
+pub const VERSION: u8 = 0x01;
+/// The minimum supported version of the protocol, version 0 peers are served with the basic
+/// commands and receive error replies without details
+pub const MIN_VERSION: u8 = 0x00;
+
+/// Capability: payload compression
+pub const CAP_COMPRESSION: u32 = 1 << 0;
 
 /// Reply command code
 pub const COMMAND_REPLY: u16 = 0x0000;
@@ -21,6 +31,27 @@ pub const COMMAND_READ_SHARED_CONTEXT: u16 = 0x0003;
 pub const COMMAND_WRITE_SHARED_CONTEXT: u16 = 0x0004;
 /// Write shared context unconfirmed command code
 pub const COMMAND_WRITE_SHARED_CONTEXT_UNCONFIRMED: u16 = 0x0005;
+/// Read shared context with register metadata command code
+pub const COMMAND_READ_SHARED_CONTEXT_METADATA: u16 = 0x0006;
+/// Read register history command code
+pub const COMMAND_READ_HISTORY: u16 = 0x0007;
+/// Read shared context with value quality command code
+pub const COMMAND_READ_SHARED_CONTEXT_QUALITY: u16 = 0x0008;
+/// Redundancy status command code
+pub const COMMAND_REDUNDANCY_STATUS: u16 = 0x0009;
+/// Host discovery command code
+pub const COMMAND_DISCOVER: u16 = 0x000A;
+/// Protocol version and capability negotiation command code
+pub const COMMAND_HELLO: u16 = 0x000B;
+/// Heartbeat command code
+pub const COMMAND_HEARTBEAT: u16 = 0x000C;
+/// Deadline envelope command code
+pub const COMMAND_DEADLINE: u16 = 0x000D;
+/// List custom commands command code
+pub const COMMAND_LIST_COMMANDS: u16 = 0x000E;
+
+/// The first custom command code
+pub const COMMAND_CUSTOM_MIN: u16 = 0x8000;
 
 /// Standard commands
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -37,6 +68,29 @@ pub enum Command {
     WriteSharedContext,
     /// Write shared context with no reply (push), carries [`RawDataHeader`] and the data
     WriteSharedContextUnconfirmed,
+    /// Read shared context with register metadata, carries [`MetadataReadHeader`], the reply
+    /// carries [`RegisterMetadata`] and the data (if changed)
+    ReadSharedContextMetadata,
+    /// Read register history, carries [`HistoryReadHeader`], the reply carries [`HistoryReply`]
+    ReadHistory,
+    /// Read shared context with value quality, carries [`RawDataHeader`], the reply carries
+    /// [`Quality`] (u8) and the data
+    ReadSharedContextQuality,
+    /// Redundancy status, carries no data, the reply carries
+    /// [`crate::redundancy::RedundancyStatus`]
+    RedundancyStatus,
+    /// Host discovery, carries no data, the reply carries [`DiscoveryReply`]
+    Discover,
+    /// Protocol version and capability negotiation, carries [`Hello`], the reply carries
+    /// [`Session`]
+    Hello,
+    /// Client heartbeat, carries [`Heartbeat`], the reply carries no data
+    Heartbeat,
+    /// Deadline envelope, carries [`DeadlineHeader`] and the data of the enclosed command. The
+    /// request is dropped by the host if the deadline has passed
+    Deadline,
+    /// List registered custom commands, carries no data, the reply carries [`CommandList`]
+    ListCommands,
 
     /// Custom commands starting from 0x8000
     Other(u16),
@@ -51,6 +105,15 @@ impl From<u16> for Command {
             COMMAND_READ_SHARED_CONTEXT => Self::ReadSharedContext,
             COMMAND_WRITE_SHARED_CONTEXT => Self::WriteSharedContext,
             COMMAND_WRITE_SHARED_CONTEXT_UNCONFIRMED => Self::WriteSharedContextUnconfirmed,
+            COMMAND_READ_SHARED_CONTEXT_METADATA => Self::ReadSharedContextMetadata,
+            COMMAND_READ_HISTORY => Self::ReadHistory,
+            COMMAND_READ_SHARED_CONTEXT_QUALITY => Self::ReadSharedContextQuality,
+            COMMAND_REDUNDANCY_STATUS => Self::RedundancyStatus,
+            COMMAND_DISCOVER => Self::Discover,
+            COMMAND_HELLO => Self::Hello,
+            COMMAND_HEARTBEAT => Self::Heartbeat,
+            COMMAND_DEADLINE => Self::Deadline,
+            COMMAND_LIST_COMMANDS => Self::ListCommands,
             _ => Self::Other(value),
         }
     }
@@ -66,6 +129,15 @@ impl Command {
             Self::ReadSharedContext => COMMAND_READ_SHARED_CONTEXT,
             Self::WriteSharedContext => COMMAND_WRITE_SHARED_CONTEXT,
             Self::WriteSharedContextUnconfirmed => COMMAND_WRITE_SHARED_CONTEXT_UNCONFIRMED,
+            Self::ReadSharedContextMetadata => COMMAND_READ_SHARED_CONTEXT_METADATA,
+            Self::ReadHistory => COMMAND_READ_HISTORY,
+            Self::ReadSharedContextQuality => COMMAND_READ_SHARED_CONTEXT_QUALITY,
+            Self::RedundancyStatus => COMMAND_REDUNDANCY_STATUS,
+            Self::Discover => COMMAND_DISCOVER,
+            Self::Hello => COMMAND_HELLO,
+            Self::Heartbeat => COMMAND_HEARTBEAT,
+            Self::Deadline => COMMAND_DEADLINE,
+            Self::ListCommands => COMMAND_LIST_COMMANDS,
             Self::Other(value) => value,
         }
     }
@@ -74,18 +146,31 @@ impl Command {
 /// Packet structure
 #[derive(Debug, Clone)]
 pub struct Packet {
+    version: u8,
     frame: Frame,
     data_len: usize,
 }
 
 impl Packet {
-    /// Create a new packet
+    /// Create a new packet of the current protocol version
     pub fn new(frame: Frame, data_len: usize) -> Self {
-        Self { frame, data_len }
+        Self {
+            version: VERSION,
+            frame,
+            data_len,
+        }
+    }
+    /// Set the protocol version the packet is framed with (e.g. the negotiated one)
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
     }
     /// Write the packet to a writer
     pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
-        let packet_header = PacketHeader::new(u32::try_from(self.data_len + Frame::SIZE)?);
+        let packet_header = PacketHeader {
+            version: self.version,
+            size: u32::try_from(self.data_len + Frame::SIZE)?,
+        };
         let mut buffer = [0u8; PacketHeader::SIZE + Frame::SIZE];
         let mut cursor = Cursor::new(&mut buffer[..]);
         packet_header.write(&mut cursor)?;
@@ -98,9 +183,7 @@ impl Packet {
         let mut header_buffer = [0u8; PacketHeader::SIZE];
         reader.read_exact(&mut header_buffer)?;
         let header = PacketHeader::read(&mut Cursor::new(&header_buffer))?;
-        if header.version != VERSION {
-            return Err(Error::UnsupportedVersion);
-        }
+        header.check_version()?;
         if header.size < u32::try_from(Frame::SIZE)? {
             return Err(Error::InvalidData);
         }
@@ -108,10 +191,15 @@ impl Packet {
         reader.read_exact(&mut frame_buffer)?;
         let frame = Frame::read(&mut Cursor::new(&frame_buffer))?;
         Ok(Self {
+            version: header.version,
             frame,
             data_len: usize::try_from(header.size)? - Frame::SIZE,
         })
     }
+    /// The protocol version the packet is framed with
+    pub fn version(&self) -> u8 {
+        self.version
+    }
     /// The packet frame data
     pub fn frame(&self) -> &Frame {
         &self.frame
@@ -151,7 +239,7 @@ impl PacketHeader {
 
     /// Check the protocol version is supported
     pub fn check_version(&self) -> Result<(), Error> {
-        if self.version != VERSION {
+        if !(MIN_VERSION..=VERSION).contains(&self.version) {
             return Err(Error::UnsupportedVersion);
         }
         Ok(())
@@ -178,6 +266,13 @@ pub struct Frame {
 impl Frame {
     /// The size of the frame header
     pub const SIZE: usize = 19;
+    /// The request command, unwrapping the [`Command::Deadline`] envelope
+    pub fn enclosed_command(&self, data: &[u8]) -> Result<Command, Error> {
+        if self.command == Command::Deadline {
+            return Ok(DeadlineHeader::read(&mut Cursor::new(data))?.command);
+        }
+        Ok(self.command)
+    }
     /// Convert the frame to a reply frame
     pub fn to_reply(&self, id: u32, error: bool) -> Self {
         Self {
@@ -212,6 +307,319 @@ impl RawDataHeader {
     pub const SIZE: usize = 12;
 }
 
+/// Deadline envelope header structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone)]
+pub struct DeadlineHeader {
+    /// The request deadline (nanoseconds since the UNIX epoch)
+    pub deadline: u64,
+    /// The enclosed command
+    pub command: Command,
+}
+
+impl DeadlineHeader {
+    /// The size of the deadline header
+    pub const SIZE: usize = 10;
+
+    /// Create a new deadline header
+    pub fn new(deadline: SystemTime, command: Command) -> Self {
+        Self {
+            deadline: deadline
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX)),
+            command,
+        }
+    }
+    /// Check if the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        UNIX_EPOCH + Duration::from_nanos(self.deadline) < SystemTime::now()
+    }
+}
+
+/// Metadata read header structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone)]
+pub struct MetadataReadHeader {
+    /// The register address
+    pub register: u32,
+    /// The offset within the register
+    pub offset: u32,
+    /// The size of the data
+    pub size: u32,
+    /// Return the data only if the register version is greater, zero to return unconditionally
+    pub since_version: u64,
+}
+
+impl MetadataReadHeader {
+    /// The size of the metadata read header
+    pub const SIZE: usize = 20;
+}
+
+/// Register metadata structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct RegisterMetadata {
+    /// The register version, incremented on each write, zero if never written
+    pub version: u64,
+    /// The last write timestamp (nanoseconds since the UNIX epoch), zero if never written
+    pub timestamp: u64,
+    /// The last writer source id
+    pub source: u32,
+}
+
+impl RegisterMetadata {
+    /// The size of the register metadata
+    pub const SIZE: usize = 20;
+
+    /// The last write time
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.timestamp)
+    }
+}
+
+/// Register value quality
+#[binrw]
+#[brw(little, repr = u8)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Quality {
+    /// The value is good
+    #[default]
+    Good = 0,
+    /// The value is uncertain
+    Uncertain = 1,
+    /// The value is bad (e.g. sensor failure)
+    Bad = 2,
+    /// The value has not been updated for too long
+    Stale = 3,
+}
+
+/// History read header structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone)]
+pub struct HistoryReadHeader {
+    /// The register address
+    pub register: u32,
+    /// The time range start (nanoseconds since the UNIX epoch, inclusive)
+    pub from: u64,
+    /// The time range end (nanoseconds since the UNIX epoch, inclusive)
+    pub to: u64,
+    /// The maximum number of samples to return (oldest first), zero for no limit
+    pub max_samples: u32,
+}
+
+impl HistoryReadHeader {
+    /// The size of the history read header
+    pub const SIZE: usize = 24;
+}
+
+/// Register history sample structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HistorySample {
+    /// The sample timestamp (nanoseconds since the UNIX epoch)
+    pub timestamp: u64,
+    #[bw(try_calc(u32::try_from(data.len())))]
+    len: u32,
+    /// The register data
+    #[br(count = len)]
+    pub data: Vec<u8>,
+}
+
+impl HistorySample {
+    /// Create a new history sample
+    pub fn new(timestamp: u64, data: Vec<u8>) -> Self {
+        Self { timestamp, data }
+    }
+    /// The sample time
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.timestamp)
+    }
+}
+
+/// Register history reply structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Default)]
+pub struct HistoryReply {
+    #[bw(try_calc(u32::try_from(samples.len())))]
+    count: u32,
+    /// The history samples, oldest first
+    #[br(count = count)]
+    pub samples: Vec<HistorySample>,
+}
+
+impl HistoryReply {
+    /// Create a new history reply
+    pub fn new(samples: Vec<HistorySample>) -> Self {
+        Self { samples }
+    }
+}
+
+/// Hello (negotiation request) structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Hello {
+    /// The minimum protocol version supported by the client
+    pub min_version: u8,
+    /// The maximum protocol version supported by the client
+    pub max_version: u8,
+    /// The capabilities supported by the client (`CAP_*` bits)
+    pub capabilities: u32,
+}
+
+impl Hello {
+    /// Create a new hello request for the current protocol versions
+    pub fn new(capabilities: u32) -> Self {
+        Self {
+            min_version: MIN_VERSION,
+            max_version: VERSION,
+            capabilities,
+        }
+    }
+    /// Agree on the highest common version and the common capabilities
+    pub fn negotiate(&self, capabilities: u32) -> Result<Session, Error> {
+        let version = std::cmp::min(self.max_version, VERSION);
+        if version < self.min_version || !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(Error::UnsupportedVersion);
+        }
+        Ok(Session {
+            version,
+            capabilities: self.capabilities & capabilities,
+        })
+    }
+}
+
+/// Heartbeat structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Heartbeat {
+    /// The maximum interval until the next heartbeat (milliseconds), zero to disarm the watchdog
+    pub interval_ms: u32,
+}
+
+impl Heartbeat {
+    /// Create a new heartbeat, the interval is rounded up to whole milliseconds, so non-zero
+    /// intervals do not disarm the watchdog
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval_ms: u32::try_from(interval.as_nanos().div_ceil(1_000_000)).unwrap_or(u32::MAX),
+        }
+    }
+    /// The heartbeat interval
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms.into())
+    }
+}
+
+/// Negotiated session parameters structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Session {
+    /// The agreed protocol version
+    pub version: u8,
+    /// The agreed capabilities (`CAP_*` bits)
+    pub capabilities: u32,
+}
+
+impl Session {
+    /// Check if a capability has been agreed
+    pub fn has(&self, capability: u32) -> bool {
+        self.capabilities & capability == capability
+    }
+}
+
+/// Register range structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RegisterRange {
+    /// The first register
+    pub first: u32,
+    /// The number of registers
+    pub count: u32,
+}
+
+/// Host discovery reply structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DiscoveryReply {
+    /// The host id
+    pub host_id: u32,
+    /// The host protocol version
+    pub version: u8,
+    #[bw(try_calc(u16::try_from(name.len())))]
+    name_len: u16,
+    /// The device name
+    #[br(count = name_len, try_map = String::from_utf8)]
+    #[bw(map = |s: &String| s.as_bytes().to_vec())]
+    pub name: String,
+    #[bw(try_calc(u16::try_from(description.len())))]
+    description_len: u16,
+    /// The device description
+    #[br(count = description_len, try_map = String::from_utf8)]
+    #[bw(map = |s: &String| s.as_bytes().to_vec())]
+    pub description: String,
+    #[bw(try_calc(u16::try_from(registers.len())))]
+    registers_len: u16,
+    /// Register summary (optional, may be empty)
+    #[br(count = registers_len)]
+    pub registers: Vec<RegisterRange>,
+}
+
+/// Custom command information structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CommandInfo {
+    /// The command code
+    pub code: u16,
+    #[bw(try_calc(u16::try_from(name.len())))]
+    name_len: u16,
+    /// The command name
+    #[br(count = name_len, try_map = String::from_utf8)]
+    #[bw(map = |s: &String| s.as_bytes().to_vec())]
+    pub name: String,
+}
+
+impl CommandInfo {
+    /// Create a new command information
+    pub fn new(code: u16, name: &str) -> Self {
+        Self {
+            code,
+            name: name.to_owned(),
+        }
+    }
+}
+
+/// Custom command list structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CommandList {
+    #[bw(try_calc(u16::try_from(commands.len())))]
+    commands_len: u16,
+    /// Registered custom commands
+    #[br(count = commands_len)]
+    pub commands: Vec<CommandInfo>,
+}
+
+impl CommandList {
+    /// Create a new command list
+    pub fn new(commands: Vec<CommandInfo>) -> Self {
+        Self { commands }
+    }
+}
+
 // Additinal impls for Command
 
 impl BinRead for Command {
@@ -0,0 +1,65 @@
+use std::borrow::Cow;
+
+use crate::comm::{Session, CAP_COMPRESSION};
+use crate::error::Error;
+use crate::Result;
+
+/// Payload encoding flag: raw data
+const ENCODING_RAW: u8 = 0;
+/// Payload encoding flag: LZ4 block with the uncompressed size prepended
+const ENCODING_LZ4: u8 = 1;
+
+/// The default payload size above which payloads are compressed
+pub(crate) const DEFAULT_THRESHOLD: usize = 1024;
+
+/// The maximum accepted uncompressed payload size
+#[cfg(feature = "compression")]
+const MAX_UNCOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Payload compression is supported by the crate build
+pub(crate) const SUPPORTED: bool = cfg!(feature = "compression");
+
+/// Check if payloads are encoded in the negotiated session. When compression is negotiated, each
+/// payload is prefixed with a single encoding flag byte
+pub(crate) fn is_negotiated(session: Option<Session>) -> bool {
+    SUPPORTED && session.is_some_and(|s| s.has(CAP_COMPRESSION))
+}
+
+/// Encode a payload, returns the encoding flag and the encoded data. The payload is compressed
+/// only if it is larger than the threshold and compression actually reduces its size
+pub(crate) fn encode(data: &[u8], threshold: usize) -> (u8, Cow<'_, [u8]>) {
+    #[cfg(feature = "compression")]
+    if data.len() > threshold {
+        let compressed = lz4_flex::compress_prepend_size(data);
+        if compressed.len() < data.len() {
+            return (ENCODING_LZ4, Cow::Owned(compressed));
+        }
+    }
+    #[cfg(not(feature = "compression"))]
+    let _ = threshold;
+    (ENCODING_RAW, Cow::Borrowed(data))
+}
+
+/// Decode a payload prefixed with the encoding flag
+pub(crate) fn decode(data: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let Some((&encoding, data)) = data.split_first() else {
+        return Err(Error::InvalidData);
+    };
+    match encoding {
+        ENCODING_RAW => Ok(Cow::Borrowed(data)),
+        #[cfg(feature = "compression")]
+        ENCODING_LZ4 => {
+            let (size, _) = lz4_flex::block::uncompressed_size(data)
+                .map_err(|e| Error::failed(format!("decompression failed: {}", e)))?;
+            if size > MAX_UNCOMPRESSED_SIZE {
+                return Err(Error::Overflow);
+            }
+            lz4_flex::decompress_size_prepended(data)
+                .map(Cow::Owned)
+                .map_err(|e| Error::failed(format!("decompression failed: {}", e)))
+        }
+        #[cfg(not(feature = "compression"))]
+        ENCODING_LZ4 => Err(Error::failed("compression not supported")),
+        _ => Err(Error::InvalidData),
+    }
+}
@@ -1,9 +1,34 @@
+use std::ops::{Bound, RangeBounds};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{io::Cursor, sync::Arc};
 
+use crate::comm::{HistorySample, Quality, RegisterMetadata};
 use crate::error::Error;
 use crate::{Mutex, Result};
 use binrw::{BinRead, BinWrite};
 
+mod composite;
+mod dirty;
+mod history;
+mod image;
+mod quality;
+mod remote;
+mod sparse;
+mod validate;
+mod version;
+mod watch;
+
+pub use composite::Composite;
+pub use dirty::{DirtyRange, DirtyTracked};
+pub use history::{History, Recording};
+pub use image::ProcessImage;
+pub use quality::Qualified;
+pub use remote::Remote;
+pub use sparse::Sparse;
+pub use validate::{Constraint, Validated, ValueType};
+pub use version::Versioned;
+pub use watch::{Watched, WriteEvent, WriteEventReceiver};
+
 /// A shared data context trait
 #[allow(clippy::module_name_repetitions)]
 pub trait RpdoContext {
@@ -11,6 +36,120 @@ pub trait RpdoContext {
     fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>>;
     /// Set data to a register
     fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()>;
+    /// Set data to a register on behalf of a source (remote host id)
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let _ = source;
+        self.set_bytes(register, offset, data)
+    }
+    /// Get register metadata and data. The data is returned only if `since_version` is zero or the
+    /// register version is greater than `since_version`
+    fn get_bytes_with_metadata(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+        since_version: u64,
+    ) -> Result<(RegisterMetadata, Option<Vec<u8>>)> {
+        let _ = (register, offset, data_size, since_version);
+        Err(Error::InvalidCommand)
+    }
+    /// Get register history samples within the time range (nanoseconds since the UNIX epoch,
+    /// inclusive), oldest first. If `max_samples` is not zero, at most `max_samples` are returned
+    fn get_history(
+        &self,
+        register: u32,
+        from: u64,
+        to: u64,
+        max_samples: u32,
+    ) -> Result<Vec<HistorySample>> {
+        let _ = (register, from, to, max_samples);
+        Err(Error::InvalidCommand)
+    }
+    /// Get data from a register together with the value quality. Contexts which do not track
+    /// quality report all values as [`Quality::Good`]
+    fn get_bytes_with_quality(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+    ) -> Result<(Quality, Vec<u8>)> {
+        Ok((Quality::Good, self.get_bytes(register, offset, data_size)?))
+    }
+}
+
+/// Implement [`RpdoContext`] methods by forwarding them to the wrapped context, e.g.
+/// `forward_context!(context => get_bytes, get_history);`. Wrappers forward all methods they do not
+/// override, so the default implementations of the trait are never used for the wrapped context
+macro_rules! forward_context {
+    ($($field:ident).+ => $($method:ident),+ $(,)?) => {
+        $crate::context::forward_context!(@path [$($field).+] $($method),+);
+    };
+    (@path $path:tt $($method:ident),+) => {
+        $($crate::context::forward_context!(@method $path $method);)+
+    };
+    (@method [$($field:ident).+] get_bytes) => {
+        fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> $crate::Result<Vec<u8>> {
+            self.$($field).+.get_bytes(register, offset, data_size)
+        }
+    };
+    (@method [$($field:ident).+] set_bytes) => {
+        fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> $crate::Result<()> {
+            self.$($field).+.set_bytes(register, offset, data)
+        }
+    };
+    (@method [$($field:ident).+] set_bytes_from) => {
+        fn set_bytes_from(
+            &self,
+            source: u32,
+            register: u32,
+            offset: u32,
+            data: &[u8],
+        ) -> $crate::Result<()> {
+            self.$($field).+.set_bytes_from(source, register, offset, data)
+        }
+    };
+    (@method [$($field:ident).+] get_bytes_with_metadata) => {
+        fn get_bytes_with_metadata(
+            &self,
+            register: u32,
+            offset: u32,
+            data_size: u32,
+            since_version: u64,
+        ) -> $crate::Result<($crate::comm::RegisterMetadata, Option<Vec<u8>>)> {
+            self.$($field).+
+                .get_bytes_with_metadata(register, offset, data_size, since_version)
+        }
+    };
+    (@method [$($field:ident).+] get_history) => {
+        fn get_history(
+            &self,
+            register: u32,
+            from: u64,
+            to: u64,
+            max_samples: u32,
+        ) -> $crate::Result<Vec<$crate::comm::HistorySample>> {
+            self.$($field).+.get_history(register, from, to, max_samples)
+        }
+    };
+    (@method [$($field:ident).+] get_bytes_with_quality) => {
+        fn get_bytes_with_quality(
+            &self,
+            register: u32,
+            offset: u32,
+            data_size: u32,
+        ) -> $crate::Result<($crate::comm::Quality, Vec<u8>)> {
+            self.$($field).+.get_bytes_with_quality(register, offset, data_size)
+        }
+    };
+}
+
+pub(crate) use forward_context;
+
+/// Current system time as nanoseconds since the UNIX epoch
+pub(crate) fn now_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_nanos()).unwrap_or(u64::MAX))
 }
 
 /// A basic implementation of a shared data context
@@ -54,46 +193,71 @@ impl Basic {
 
 impl RpdoContext for Basic {
     fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
-        let register = usize::try_from(register).unwrap();
-        let Some(reg_data) = self.data.get(register) else {
+        let index = usize::try_from(register).unwrap();
+        let Some(reg_data) = self.data.get(index) else {
             return Err(Error::InvalidRegister);
         };
-        let mut reg_data = reg_data.lock();
-        let offset = usize::try_from(offset).unwrap();
-        if reg_data.len() < offset + data.len() {
-            if !self.register_flexible {
-                return Err(Error::InvalidOffset);
-            }
-            reg_data.resize(offset + data.len(), 0);
-        }
-        reg_data[offset..offset + data.len()].copy_from_slice(data);
-        Ok(())
+        write_data(&mut reg_data.lock(), offset, data, self.register_flexible)
     }
     fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>> {
-        let register = usize::try_from(register).unwrap();
-        let Some(reg_data) = self.data.get(register) else {
+        let index = usize::try_from(register).unwrap();
+        let Some(reg_data) = self.data.get(index) else {
             return Err(Error::InvalidRegister);
         };
-        let reg_data = reg_data.lock();
-        let offset = usize::try_from(offset).unwrap();
-        let mut data_size = usize::try_from(data_size).unwrap();
-        if data_size == 0 {
-            data_size = reg_data.len() - offset;
+        read_data(&reg_data.lock(), offset, data_size, self.register_flexible)
+    }
+}
+
+/// Write data to a register buffer, resizing it if flexible
+fn write_data(reg_data: &mut Vec<u8>, offset: u32, data: &[u8], flexible: bool) -> Result<()> {
+    let offset = usize::try_from(offset).unwrap();
+    if reg_data.len() < offset + data.len() {
+        if !flexible {
+            return Err(Error::InvalidOffset);
         }
-        if offset > reg_data.len() {
-            if !self.register_flexible {
-                return Err(Error::InvalidOffset);
-            }
-            return Ok(vec![0; data_size]);
+        reg_data.resize(offset + data.len(), 0);
+    }
+    reg_data[offset..offset + data.len()].copy_from_slice(data);
+    Ok(())
+}
+
+/// Read data from a register buffer, zero-padding the result if flexible
+fn read_data(reg_data: &[u8], offset: u32, data_size: u32, flexible: bool) -> Result<Vec<u8>> {
+    let offset = usize::try_from(offset).unwrap();
+    let mut data_size = usize::try_from(data_size).unwrap();
+    if data_size == 0 {
+        data_size = reg_data.len().saturating_sub(offset);
+    }
+    if offset > reg_data.len() {
+        if !flexible {
+            return Err(Error::InvalidOffset);
         }
-        let mut result = reg_data[offset..reg_data.len().min(offset + data_size)].to_vec();
-        drop(reg_data);
-        if result.len() < data_size {
-            if !self.register_flexible {
-                return Err(Error::InvalidOffset);
-            }
-            result.resize(data_size, 0);
+        return Ok(vec![0; data_size]);
+    }
+    let mut result = reg_data[offset..reg_data.len().min(offset + data_size)].to_vec();
+    if result.len() < data_size {
+        if !flexible {
+            return Err(Error::InvalidOffset);
         }
-        Ok(result)
+        result.resize(data_size, 0);
+    }
+    Ok(result)
+}
+
+/// Convert register range bounds to the first and the last register numbers
+fn range_bounds(registers: &impl RangeBounds<u32>) -> Result<(u32, u32)> {
+    let first = match registers.start_bound() {
+        Bound::Included(v) => *v,
+        Bound::Excluded(v) => v.checked_add(1).ok_or(Error::Overflow)?,
+        Bound::Unbounded => 0,
+    };
+    let last = match registers.end_bound() {
+        Bound::Included(v) => *v,
+        Bound::Excluded(v) => v.checked_sub(1).ok_or(Error::Overflow)?,
+        Bound::Unbounded => u32::MAX,
+    };
+    if first > last {
+        return Err(Error::failed("empty register range"));
     }
+    Ok((first, last))
 }
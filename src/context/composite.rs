@@ -0,0 +1,132 @@
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use super::{range_bounds, RpdoContext};
+use crate::comm::{HistorySample, Quality, RegisterMetadata};
+use crate::error::Error;
+use crate::Result;
+
+#[derive(Clone)]
+struct Mount {
+    first: u32,
+    last: u32,
+    base: u32,
+    context: Arc<dyn RpdoContext + Send + Sync>,
+}
+
+/// A context which mounts multiple backends at register ranges
+///
+/// Registers which are not covered by any mount return [`Error::InvalidRegister`].
+#[derive(Clone, Default)]
+pub struct Composite {
+    mounts: Vec<Mount>,
+}
+
+impl Composite {
+    /// Create a new empty composite context
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Mount a context at the register range, the backend registers have the same numbers
+    pub fn mount<C>(&mut self, registers: impl RangeBounds<u32>, context: C) -> Result<()>
+    where
+        C: RpdoContext + Send + Sync + 'static,
+    {
+        let (first, _) = range_bounds(&registers)?;
+        self.mount_renumbered(registers, first, context)
+    }
+    /// Mount a context at the register range, the first register of the range is mapped to the
+    /// backend register `base`
+    pub fn mount_renumbered<C>(
+        &mut self,
+        registers: impl RangeBounds<u32>,
+        base: u32,
+        context: C,
+    ) -> Result<()>
+    where
+        C: RpdoContext + Send + Sync + 'static,
+    {
+        let (first, last) = range_bounds(&registers)?;
+        if base.checked_add(last - first).is_none() {
+            return Err(Error::Overflow);
+        }
+        if self
+            .mounts
+            .iter()
+            .any(|m| first <= m.last && m.first <= last)
+        {
+            return Err(Error::failed(format!(
+                "register range {}-{} overlaps an existing mount",
+                first, last
+            )));
+        }
+        let pos = self.mounts.partition_point(|m| m.first < first);
+        self.mounts.insert(
+            pos,
+            Mount {
+                first,
+                last,
+                base,
+                context: Arc::new(context),
+            },
+        );
+        Ok(())
+    }
+    fn resolve(&self, register: u32) -> Result<(&dyn RpdoContext, u32)> {
+        let pos = self.mounts.partition_point(|m| m.first <= register);
+        let Some(mount) = pos.checked_sub(1).map(|p| &self.mounts[p]) else {
+            return Err(Error::InvalidRegister);
+        };
+        if register > mount.last {
+            return Err(Error::InvalidRegister);
+        }
+        Ok((
+            mount.context.as_ref(),
+            mount.base + (register - mount.first),
+        ))
+    }
+}
+
+impl RpdoContext for Composite {
+    fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>> {
+        let (context, register) = self.resolve(register)?;
+        context.get_bytes(register, offset, data_size)
+    }
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let (context, register) = self.resolve(register)?;
+        context.set_bytes(register, offset, data)
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let (context, register) = self.resolve(register)?;
+        context.set_bytes_from(source, register, offset, data)
+    }
+    fn get_bytes_with_metadata(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+        since_version: u64,
+    ) -> Result<(RegisterMetadata, Option<Vec<u8>>)> {
+        let (context, register) = self.resolve(register)?;
+        context.get_bytes_with_metadata(register, offset, data_size, since_version)
+    }
+    fn get_history(
+        &self,
+        register: u32,
+        from: u64,
+        to: u64,
+        max_samples: u32,
+    ) -> Result<Vec<HistorySample>> {
+        let (context, register) = self.resolve(register)?;
+        context.get_history(register, from, to, max_samples)
+    }
+    fn get_bytes_with_quality(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+    ) -> Result<(Quality, Vec<u8>)> {
+        let (context, register) = self.resolve(register)?;
+        context.get_bytes_with_quality(register, offset, data_size)
+    }
+}
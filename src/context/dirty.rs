@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::mem;
+use std::sync::Arc;
+
+use super::{forward_context, RpdoContext};
+use crate::error::Error;
+use crate::io::SimpleClient;
+use crate::{Mutex, Result};
+
+/// A changed register data range
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DirtyRange {
+    /// The register
+    pub register: u32,
+    /// The offset within the register
+    pub offset: u32,
+    /// The range length
+    pub len: u32,
+}
+
+// register -> sorted non-overlapping (start, end) ranges
+type DirtyMap = BTreeMap<u32, Vec<(u32, u32)>>;
+
+/// A context wrapper which tracks changed register data ranges
+///
+/// Overlapping and adjacent ranges of a register are merged. The ranges can be taken with
+/// [`DirtyTracked::take_dirty`] or pushed to a remote host with [`DirtyTracked::sync_to`].
+pub struct DirtyTracked<CTX>
+where
+    CTX: RpdoContext,
+{
+    context: CTX,
+    dirty: Arc<Mutex<DirtyMap>>,
+}
+
+impl<CTX> Clone for DirtyTracked<CTX>
+where
+    CTX: RpdoContext + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            dirty: self.dirty.clone(),
+        }
+    }
+}
+
+impl<CTX> DirtyTracked<CTX>
+where
+    CTX: RpdoContext,
+{
+    /// Create a new dirty-tracked context
+    pub fn new(context: CTX) -> Self {
+        Self {
+            context,
+            dirty: <_>::default(),
+        }
+    }
+    /// The inner context
+    pub fn context(&self) -> &CTX {
+        &self.context
+    }
+    /// Mark a register data range as changed (e.g. to force the initial full sync). Fails if the
+    /// range end does not fit `u32`
+    pub fn mark_dirty(&self, register: u32, offset: u32, len: u32) -> Result<()> {
+        let mut end = range_end(offset, len)?;
+        if len == 0 {
+            return Ok(());
+        }
+        let mut dirty = self.dirty.lock();
+        let ranges = dirty.entry(register).or_default();
+        let mut start = offset;
+        let first = ranges.partition_point(|&(_, e)| e < start);
+        let mut last = first;
+        while last < ranges.len() && ranges[last].0 <= end {
+            start = start.min(ranges[last].0);
+            end = end.max(ranges[last].1);
+            last += 1;
+        }
+        ranges.splice(first..last, [(start, end)]);
+        Ok(())
+    }
+    /// Check if there are changed ranges
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.lock().is_empty()
+    }
+    /// Take all changed ranges since the last call
+    pub fn take_dirty(&self) -> Vec<DirtyRange> {
+        let dirty = mem::take(&mut *self.dirty.lock());
+        dirty
+            .into_iter()
+            .flat_map(|(register, ranges)| {
+                ranges.into_iter().map(move |(start, end)| DirtyRange {
+                    register,
+                    offset: start,
+                    len: end - start,
+                })
+            })
+            .collect()
+    }
+    /// Push changed ranges to a remote host with unconfirmed writes. Returns the number of
+    /// ranges sent. If sending fails, the unsent ranges are marked as changed again
+    pub fn sync_to<S>(&self, client: &mut SimpleClient<S>) -> Result<usize>
+    where
+        S: Read + Write,
+    {
+        let ranges = self.take_dirty();
+        for (n, range) in ranges.iter().enumerate() {
+            if let Err(e) = self
+                .context
+                .get_bytes(range.register, range.offset, range.len)
+                .and_then(|data| {
+                    client.write_register_unconfirmed(range.register, range.offset, &data)
+                })
+            {
+                for r in &ranges[n..] {
+                    // the ranges have been validated when marked
+                    let _ = self.mark_dirty(r.register, r.offset, r.len);
+                }
+                return Err(e);
+            }
+        }
+        Ok(ranges.len())
+    }
+}
+
+/// The end of a register data range, fails if it does not fit `u32`
+fn range_end(offset: u32, len: u32) -> Result<u32> {
+    offset.checked_add(len).ok_or(Error::InvalidOffset)
+}
+
+impl<CTX> RpdoContext for DirtyTracked<CTX>
+where
+    CTX: RpdoContext,
+{
+    forward_context!(context => get_bytes, get_bytes_with_metadata);
+    forward_context!(context => get_history, get_bytes_with_quality);
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let len = u32::try_from(data.len())?;
+        range_end(offset, len)?;
+        self.context.set_bytes(register, offset, data)?;
+        self.mark_dirty(register, offset, len)
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let len = u32::try_from(data.len())?;
+        range_end(offset, len)?;
+        self.context
+            .set_bytes_from(source, register, offset, data)?;
+        self.mark_dirty(register, offset, len)
+    }
+}
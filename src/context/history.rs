@@ -0,0 +1,214 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use super::{forward_context, now_ns, RpdoContext};
+use crate::comm::HistorySample;
+use crate::error::Error;
+use crate::{Mutex, Result};
+
+/// History recording mode
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Recording {
+    /// Record the register value after each successful write
+    OnWrite,
+    /// Record the register value on [`History::sample`] calls
+    Sampled,
+}
+
+struct Trend {
+    recording: Recording,
+    capacity: usize,
+    samples: VecDeque<HistorySample>,
+}
+
+impl Trend {
+    /// Insert a sample, the samples are kept ordered by timestamps as the system clock may be set
+    /// back. If the buffer is full, the oldest sample is dropped
+    fn push(&mut self, sample: HistorySample) {
+        let pos = self
+            .samples
+            .partition_point(|s| s.timestamp <= sample.timestamp);
+        if self.samples.len() < self.capacity {
+            self.samples.insert(pos, sample);
+        } else if pos > 0 {
+            self.samples.pop_front();
+            self.samples.insert(pos - 1, sample);
+        }
+    }
+}
+
+struct HistoryInner<CTX> {
+    context: CTX,
+    trends: Mutex<BTreeMap<u32, Trend>>,
+}
+
+/// A context wrapper which records timestamped values of selected registers into per-register
+/// ring buffers
+pub struct History<CTX>
+where
+    CTX: RpdoContext,
+{
+    inner: Arc<HistoryInner<CTX>>,
+}
+
+impl<CTX> Clone for History<CTX>
+where
+    CTX: RpdoContext,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<CTX> History<CTX>
+where
+    CTX: RpdoContext,
+{
+    /// Create a new history context
+    pub fn new(context: CTX) -> Self {
+        Self {
+            inner: Arc::new(HistoryInner {
+                context,
+                trends: <_>::default(),
+            }),
+        }
+    }
+    /// The inner context
+    pub fn context(&self) -> &CTX {
+        &self.inner.context
+    }
+    /// Record history of a register, keeping up to `capacity` last samples. If the register is
+    /// already recorded, its history is cleared
+    pub fn record(&self, register: u32, capacity: usize, recording: Recording) -> Result<()> {
+        if capacity == 0 {
+            return Err(Error::failed("history capacity must be greater than zero"));
+        }
+        self.inner.trends.lock().insert(
+            register,
+            Trend {
+                recording,
+                capacity,
+                samples: VecDeque::with_capacity(capacity),
+            },
+        );
+        Ok(())
+    }
+    /// Stop recording history of a register
+    pub fn stop_recording(&self, register: u32) {
+        self.inner.trends.lock().remove(&register);
+    }
+    /// Record the current values of all registers in [`Recording::Sampled`] mode. Registers which
+    /// can not be read are skipped, the first error is returned after the other registers are
+    /// sampled
+    pub fn sample(&self) -> Result<()> {
+        let mut trends = self.inner.trends.lock();
+        let timestamp = now_ns();
+        let mut result = Ok(());
+        for (register, trend) in trends.iter_mut() {
+            if trend.recording == Recording::Sampled {
+                match self.inner.context.get_bytes(*register, 0, 0) {
+                    Ok(data) => trend.push(HistorySample::new(timestamp, data)),
+                    Err(e) => {
+                        tracing::warn!(register, error = %e, "unable to sample history");
+                        if result.is_ok() {
+                            result = Err(e);
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+    /// Get register history samples within the time range (nanoseconds since the UNIX epoch,
+    /// inclusive), oldest first. If `max_samples` is not zero, at most `max_samples` are returned
+    pub fn history(
+        &self,
+        register: u32,
+        from: u64,
+        to: u64,
+        max_samples: u32,
+    ) -> Result<Vec<HistorySample>> {
+        let trends = self.inner.trends.lock();
+        let Some(trend) = trends.get(&register) else {
+            return Err(Error::InvalidRegister);
+        };
+        let limit = if max_samples == 0 {
+            usize::MAX
+        } else {
+            usize::try_from(max_samples)?
+        };
+        let start = trend.samples.partition_point(|s| s.timestamp < from);
+        Ok(trend
+            .samples
+            .range(start..)
+            .take_while(|s| s.timestamp <= to)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+    fn record_write(&self, register: u32) {
+        let mut trends = self.inner.trends.lock();
+        let Some(trend) = trends.get_mut(&register) else {
+            return;
+        };
+        if trend.recording != Recording::OnWrite {
+            return;
+        }
+        match self.inner.context.get_bytes(register, 0, 0) {
+            Ok(data) => trend.push(HistorySample::new(now_ns(), data)),
+            Err(e) => tracing::warn!(register, error = %e, "unable to record history"),
+        }
+    }
+}
+
+impl<CTX> History<CTX>
+where
+    CTX: RpdoContext + Send + Sync + 'static,
+{
+    /// Spawn a thread which calls [`History::sample`] with the given period. The thread exits
+    /// when all history context instances are dropped
+    pub fn spawn_sampler(&self, period: Duration) -> thread::JoinHandle<()> {
+        let inner: Weak<HistoryInner<CTX>> = Arc::downgrade(&self.inner);
+        thread::spawn(move || {
+            for _ in rtsc::time::interval(period) {
+                let Some(inner) = inner.upgrade() else {
+                    break;
+                };
+                // per-register failures are reported by the sample call
+                let _ = (History { inner }).sample();
+            }
+        })
+    }
+}
+
+impl<CTX> RpdoContext for History<CTX>
+where
+    CTX: RpdoContext,
+{
+    forward_context!(inner.context => get_bytes, get_bytes_with_metadata, get_bytes_with_quality);
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.inner.context.set_bytes(register, offset, data)?;
+        self.record_write(register);
+        Ok(())
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.inner
+            .context
+            .set_bytes_from(source, register, offset, data)?;
+        self.record_write(register);
+        Ok(())
+    }
+    fn get_history(
+        &self,
+        register: u32,
+        from: u64,
+        to: u64,
+        max_samples: u32,
+    ) -> Result<Vec<HistorySample>> {
+        self.history(register, from, to, max_samples)
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::mem;
+use std::ops::Range;
+use std::sync::Arc;
+
+use binrw::{BinRead, BinWrite};
+
+use super::{read_data, write_data, RpdoContext};
+use crate::error::Error;
+use crate::{Mutex, Result};
+
+struct StagedWrite {
+    register: u32,
+    offset: u32,
+    data: Vec<u8>,
+}
+
+/// The default maximum number of staged remote writes
+const DEFAULT_MAX_STAGED: usize = 1024;
+
+/// The logic buffer and the ranges changed since the last commit
+#[derive(Default)]
+struct LogicBuffer {
+    registers: Vec<Vec<u8>>,
+    dirty: BTreeMap<usize, Range<usize>>,
+}
+
+impl LogicBuffer {
+    fn write(&mut self, register: u32, offset: u32, data: &[u8], flexible: bool) -> Result<()> {
+        let index = usize::try_from(register)?;
+        let Some(reg_data) = self.registers.get_mut(index) else {
+            return Err(Error::InvalidRegister);
+        };
+        write_data(reg_data, offset, data, flexible)?;
+        let start = usize::try_from(offset)?;
+        let end = start + data.len();
+        self.dirty
+            .entry(index)
+            .and_modify(|r| *r = r.start.min(start)..r.end.max(end))
+            .or_insert(start..end);
+        Ok(())
+    }
+}
+
+struct ProcessImageInner {
+    published: Mutex<Vec<Vec<u8>>>,
+    logic: Mutex<LogicBuffer>,
+    staged: Mutex<Vec<StagedWrite>>,
+    register_flexible: bool,
+}
+
+/// A double-buffered process image context with cyclic commits
+///
+/// The image has separate network and logic buffers. Remote writes (via [`RpdoContext`]) are
+/// staged and applied to the logic buffer at [`ProcessImage::commit`] only, so the control loop
+/// sees inputs frozen for the duration of a cycle. Remote reads see the logic buffer state as of
+/// the last commit, so clients always get consistent cycle snapshots.
+///
+/// The number of staged writes is limited. When the limit is reached, a write to the same range
+/// as the last staged write of the register replaces it, other writes are rejected with
+/// [`Error::Overflow`] until the next commit.
+#[derive(Clone)]
+pub struct ProcessImage {
+    inner: Arc<ProcessImageInner>,
+    max_staged: usize,
+}
+
+impl ProcessImage {
+    /// Create a new process image, `register_flexible` determines if the registers can be resized
+    /// if the length of the data is greater than the current length
+    pub fn new(register_count: usize, register_size: usize, register_flexible: bool) -> Self {
+        let image: Vec<Vec<u8>> = (0..register_count)
+            .map(|_| vec![0; register_size])
+            .collect();
+        Self {
+            inner: Arc::new(ProcessImageInner {
+                published: Mutex::new(image.clone()),
+                logic: Mutex::new(LogicBuffer {
+                    registers: image,
+                    dirty: BTreeMap::new(),
+                }),
+                staged: <_>::default(),
+                register_flexible,
+            }),
+            max_staged: DEFAULT_MAX_STAGED,
+        }
+    }
+    /// Set the maximum number of staged remote writes (default: 1024)
+    pub fn with_max_staged(mut self, max_staged: usize) -> Self {
+        self.max_staged = max_staged;
+        self
+    }
+    /// Apply staged remote writes to the logic buffer and publish the logic buffer to the network
+    /// one. Must be called by the control loop once per cycle. Returns the number of applied
+    /// writes
+    pub fn commit(&self) -> Result<usize> {
+        let staged = mem::take(&mut *self.inner.staged.lock());
+        let mut logic = self.inner.logic.lock();
+        for w in &staged {
+            if let Err(e) = logic.write(w.register, w.offset, &w.data, self.inner.register_flexible)
+            {
+                tracing::warn!(register = w.register, error = %e, "staged write rejected");
+            }
+        }
+        // the buffers are swapped, the changed ranges are copied back to the logic one
+        let LogicBuffer { registers, dirty } = &mut *logic;
+        let mut published = self.inner.published.lock();
+        mem::swap(&mut *published, registers);
+        for (index, range) in mem::take(dirty) {
+            let source = &published[index];
+            let target = &mut registers[index];
+            if target.len() < source.len() {
+                target.resize(source.len(), 0);
+            }
+            target[range.clone()].copy_from_slice(&source[range]);
+        }
+        Ok(staged.len())
+    }
+    /// Get data from a register of the logic buffer
+    pub fn get_logic_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>> {
+        let logic = self.inner.logic.lock();
+        let Some(reg_data) = logic.registers.get(usize::try_from(register)?) else {
+            return Err(Error::InvalidRegister);
+        };
+        read_data(reg_data, offset, data_size, self.inner.register_flexible)
+    }
+    /// Set data to a register of the logic buffer
+    pub fn set_logic_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.inner
+            .logic
+            .lock()
+            .write(register, offset, data, self.inner.register_flexible)
+    }
+    /// Get and unpack a value from a register of the logic buffer
+    pub fn get<T>(&self, register: u32, offset: u32, data_size: u32) -> Result<T>
+    where
+        T: for<'a> BinRead<Args<'a> = ()>,
+    {
+        let mut c = Cursor::new(self.get_logic_bytes(register, offset, data_size)?);
+        T::read_le(&mut c).map_err(Into::into)
+    }
+    /// Pack and set a value to a register of the logic buffer
+    pub fn set<T>(&self, register: u32, offset: u32, data: &T) -> Result<()>
+    where
+        T: for<'a> BinWrite<Args<'a> = ()>,
+    {
+        let mut c = Cursor::new(Vec::new());
+        data.write_le(&mut c)?;
+        self.set_logic_bytes(register, offset, &c.into_inner())
+    }
+    /// Number of staged remote writes waiting for a commit
+    pub fn staged_count(&self) -> usize {
+        self.inner.staged.lock().len()
+    }
+}
+
+impl RpdoContext for ProcessImage {
+    fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>> {
+        let published = self.inner.published.lock();
+        let Some(reg_data) = published.get(usize::try_from(register)?) else {
+            return Err(Error::InvalidRegister);
+        };
+        read_data(reg_data, offset, data_size, self.inner.register_flexible)
+    }
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        {
+            let published = self.inner.published.lock();
+            let Some(reg_data) = published.get(usize::try_from(register)?) else {
+                return Err(Error::InvalidRegister);
+            };
+            if !self.inner.register_flexible
+                && reg_data.len() < usize::try_from(offset)? + data.len()
+            {
+                return Err(Error::InvalidOffset);
+            }
+        }
+        let mut staged = self.inner.staged.lock();
+        if staged.len() >= self.max_staged {
+            // the last staged write of the register is replaced if it has the same range
+            let Some(w) = staged
+                .iter_mut()
+                .rev()
+                .find(|w| w.register == register)
+                .filter(|w| w.offset == offset && w.data.len() == data.len())
+            else {
+                return Err(Error::Overflow);
+            };
+            w.data.copy_from_slice(data);
+            return Ok(());
+        }
+        staged.push(StagedWrite {
+            register,
+            offset,
+            data: data.to_vec(),
+        });
+        Ok(())
+    }
+}
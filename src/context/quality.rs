@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{forward_context, RpdoContext};
+use crate::comm::Quality;
+use crate::{Mutex, Result};
+
+#[derive(Clone, Copy)]
+struct QualityState {
+    quality: Quality,
+    last_write: Instant,
+}
+
+/// A context wrapper which keeps a value quality for each register
+///
+/// The quality is set by the producer with [`Qualified::set_quality`]. If a stale timeout is
+/// configured, registers which have not been written for longer than the timeout are reported as
+/// [`Quality::Stale`] unless their quality is [`Quality::Bad`].
+pub struct Qualified<CTX>
+where
+    CTX: RpdoContext,
+{
+    context: CTX,
+    states: Arc<Mutex<BTreeMap<u32, QualityState>>>,
+    stale_after: Option<Duration>,
+    created: Instant,
+}
+
+impl<CTX> Clone for Qualified<CTX>
+where
+    CTX: RpdoContext + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            states: self.states.clone(),
+            stale_after: self.stale_after,
+            created: self.created,
+        }
+    }
+}
+
+impl<CTX> Qualified<CTX>
+where
+    CTX: RpdoContext,
+{
+    /// Create a new qualified context, all registers have [`Quality::Good`] by default
+    pub fn new(context: CTX) -> Self {
+        Self {
+            context,
+            states: <_>::default(),
+            stale_after: None,
+            created: Instant::now(),
+        }
+    }
+    /// Report registers with no writes for the given time as stale
+    pub fn with_stale_after(mut self, stale_after: Duration) -> Self {
+        self.stale_after = Some(stale_after);
+        self
+    }
+    /// The inner context
+    pub fn context(&self) -> &CTX {
+        &self.context
+    }
+    /// Set register value quality
+    pub fn set_quality(&self, register: u32, quality: Quality) {
+        self.states
+            .lock()
+            .entry(register)
+            .or_insert_with(|| QualityState {
+                quality,
+                last_write: self.created,
+            })
+            .quality = quality;
+    }
+    /// Get register value quality
+    pub fn quality(&self, register: u32) -> Quality {
+        let state = self.states.lock().get(&register).copied();
+        let (quality, last_write) =
+            state.map_or((Quality::Good, self.created), |s| (s.quality, s.last_write));
+        if quality == Quality::Bad {
+            return quality;
+        }
+        if let Some(stale_after) = self.stale_after {
+            if last_write.elapsed() > stale_after {
+                return Quality::Stale;
+            }
+        }
+        quality
+    }
+    fn touch(&self, register: u32) {
+        let now = Instant::now();
+        self.states
+            .lock()
+            .entry(register)
+            .or_insert(QualityState {
+                quality: Quality::Good,
+                last_write: now,
+            })
+            .last_write = now;
+    }
+}
+
+impl<CTX> RpdoContext for Qualified<CTX>
+where
+    CTX: RpdoContext,
+{
+    forward_context!(context => get_bytes, get_bytes_with_metadata, get_history);
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.context.set_bytes(register, offset, data)?;
+        self.touch(register);
+        Ok(())
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.context
+            .set_bytes_from(source, register, offset, data)?;
+        self.touch(register);
+        Ok(())
+    }
+    fn get_bytes_with_quality(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+    ) -> Result<(Quality, Vec<u8>)> {
+        let data = self.context.get_bytes(register, offset, data_size)?;
+        Ok((self.quality(register), data))
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use super::RpdoContext;
+use crate::comm::{HistorySample, Quality, RegisterMetadata};
+use crate::io::SimpleClient;
+use crate::{Mutex, Result};
+
+// register, offset, data size
+type CacheKey = (u32, u32, u32);
+
+#[derive(Default)]
+struct Cache {
+    entries: BTreeMap<CacheKey, (Instant, Vec<u8>)>,
+    // incremented on each invalidation, so reads which overlap a write are not cached
+    generations: BTreeMap<u32, u64>,
+    epoch: u64,
+}
+
+impl Cache {
+    fn generation(&self, register: u32) -> (u64, u64) {
+        (
+            self.epoch,
+            self.generations.get(&register).copied().unwrap_or_default(),
+        )
+    }
+}
+
+/// A context which forwards all requests to a remote host with a [`SimpleClient`]
+///
+/// Allows a [`crate::host::Host`] to republish registers of a downstream device. Reads can be
+/// optionally cached, the cache of a register is invalidated on each write through this context.
+/// Writes are forwarded on behalf of the original source.
+pub struct Remote<S>
+where
+    S: Read + Write,
+{
+    client: Mutex<SimpleClient<S>>,
+    cache_ttl: Option<Duration>,
+    cache: Mutex<Cache>,
+}
+
+impl<S> Remote<S>
+where
+    S: Read + Write,
+{
+    /// Create a new remote context
+    pub fn new(client: SimpleClient<S>) -> Self {
+        Self {
+            client: Mutex::new(client),
+            cache_ttl: None,
+            cache: <_>::default(),
+        }
+    }
+    /// Cache read results for the given time
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+    /// Clear the read cache
+    pub fn clear_cache(&self) {
+        let mut cache = self.cache.lock();
+        cache.entries.clear();
+        cache.epoch += 1;
+    }
+    fn invalidate(&self, register: u32) {
+        if self.cache_ttl.is_some() {
+            let mut cache = self.cache.lock();
+            cache.entries.retain(|&(reg, _, _), _| reg != register);
+            *cache.generations.entry(register).or_default() += 1;
+        }
+    }
+}
+
+impl<S> RpdoContext for Remote<S>
+where
+    S: Read + Write,
+{
+    fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>> {
+        let Some(ttl) = self.cache_ttl else {
+            return self
+                .client
+                .lock()
+                .read_register(register, offset, data_size);
+        };
+        let key = (register, offset, data_size);
+        let generation = {
+            let cache = self.cache.lock();
+            if let Some((t, data)) = cache.entries.get(&key) {
+                if t.elapsed() < ttl {
+                    return Ok(data.clone());
+                }
+            }
+            cache.generation(register)
+        };
+        let data = self
+            .client
+            .lock()
+            .read_register(register, offset, data_size)?;
+        let mut cache = self.cache.lock();
+        // the register has been written during the read, the data may be stale
+        if cache.generation(register) != generation {
+            return Ok(data);
+        }
+        cache.entries.retain(|_, (t, _)| t.elapsed() < ttl);
+        cache.entries.insert(key, (Instant::now(), data.clone()));
+        Ok(data)
+    }
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let result = self.client.lock().write_register(register, offset, data);
+        self.invalidate(register);
+        result
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let result = self
+            .client
+            .lock()
+            .write_register_from(source, register, offset, data);
+        self.invalidate(register);
+        result
+    }
+    fn get_bytes_with_metadata(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+        since_version: u64,
+    ) -> Result<(RegisterMetadata, Option<Vec<u8>>)> {
+        self.client
+            .lock()
+            .read_register_if_changed(register, offset, data_size, since_version)
+    }
+    fn get_history(
+        &self,
+        register: u32,
+        from: u64,
+        to: u64,
+        max_samples: u32,
+    ) -> Result<Vec<HistorySample>> {
+        self.client
+            .lock()
+            .read_history(register, from, to, max_samples)
+    }
+    fn get_bytes_with_quality(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+    ) -> Result<(Quality, Vec<u8>)> {
+        self.client
+            .lock()
+            .read_register_with_quality(register, offset, data_size)
+    }
+}
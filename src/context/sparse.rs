@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use binrw::{BinRead, BinWrite};
+
+use super::{read_data, write_data, RpdoContext};
+use crate::error::Error;
+use crate::{Mutex, Result};
+
+/// A register, `None` if removed
+type Register = Arc<Mutex<Option<Vec<u8>>>>;
+
+#[derive(Default)]
+struct SparseData {
+    registers: Mutex<BTreeMap<u32, Register>>,
+    memory: AtomicUsize,
+}
+
+/// A sparse shared data context, registers are kept in a map and may have arbitrary numbers
+///
+/// Each register is locked separately, so accessing different registers does not block.
+#[derive(Clone)]
+pub struct Sparse {
+    data: Arc<SparseData>,
+    register_size: usize,
+    register_flexible: bool,
+    auto_create: bool,
+    max_registers: usize,
+    max_memory: usize,
+}
+
+impl Sparse {
+    /// Create a new sparse shared data context with no registers. New registers are allocated
+    /// with `register_size` bytes, `register_flexible` determines if the registers can be resized
+    /// if the length of the data is greater than the current length
+    pub fn new(register_size: usize, register_flexible: bool) -> Self {
+        Self {
+            data: <_>::default(),
+            register_size,
+            register_flexible,
+            auto_create: false,
+            max_registers: usize::MAX,
+            max_memory: usize::MAX,
+        }
+    }
+    /// Create missing registers on the first write. A register is not created if the write fails
+    pub fn with_auto_create(mut self, auto_create: bool) -> Self {
+        self.auto_create = auto_create;
+        self
+    }
+    /// Set the maximum number of registers
+    pub fn with_max_registers(mut self, max_registers: usize) -> Self {
+        self.max_registers = max_registers;
+        self
+    }
+    /// Set the maximum total size of register data (bytes)
+    pub fn with_max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = max_memory;
+        self
+    }
+    /// Create a register, does nothing if the register already exists
+    pub fn create_register(&self, register: u32) -> Result<()> {
+        self.insert(register, vec![0; self.register_size])?;
+        Ok(())
+    }
+    /// Remove a register
+    pub fn remove_register(&self, register: u32) -> Result<()> {
+        let Some(reg) = self.data.registers.lock().remove(&register) else {
+            return Err(Error::InvalidRegister);
+        };
+        if let Some(reg_data) = reg.lock().take() {
+            self.release(reg_data.len());
+        }
+        Ok(())
+    }
+    /// Existing register numbers
+    pub fn registers(&self) -> Vec<u32> {
+        self.data.registers.lock().keys().copied().collect()
+    }
+    /// The total size of register data (bytes)
+    pub fn memory_usage(&self) -> usize {
+        self.data.memory.load(Ordering::SeqCst)
+    }
+    /// Get and unpack a value from a register
+    pub fn get<T>(&self, register: u32, offset: u32, data_size: u32) -> Result<T>
+    where
+        T: for<'a> BinRead<Args<'a> = ()>,
+    {
+        let mut c = Cursor::new(self.get_bytes(register, offset, data_size)?);
+        T::read_le(&mut c).map_err(Into::into)
+    }
+    /// Pack and set a value to a register
+    pub fn set<T>(&self, register: u32, offset: u32, data: &T) -> Result<()>
+    where
+        T: for<'a> BinWrite<Args<'a> = ()>,
+    {
+        let mut c = Cursor::new(Vec::new());
+        data.write_le(&mut c)?;
+        self.set_bytes(register, offset, &c.into_inner())
+    }
+    fn register(&self, register: u32) -> Option<Register> {
+        self.data.registers.lock().get(&register).cloned()
+    }
+    /// Insert a new register with the data, returns false if the register already exists
+    fn insert(&self, register: u32, reg_data: Vec<u8>) -> Result<bool> {
+        let mut registers = self.data.registers.lock();
+        if registers.contains_key(&register) {
+            return Ok(false);
+        }
+        if registers.len() >= self.max_registers {
+            return Err(Error::Overflow);
+        }
+        self.reserve(reg_data.len())?;
+        registers.insert(register, Arc::new(Mutex::new(Some(reg_data))));
+        Ok(true)
+    }
+    /// Account register data memory, fails if the limit is exceeded
+    fn reserve(&self, size: usize) -> Result<()> {
+        self.data
+            .memory
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |memory| {
+                memory.checked_add(size).filter(|v| *v <= self.max_memory)
+            })
+            .map(|_| ())
+            .map_err(|_| Error::Overflow)
+    }
+    fn release(&self, size: usize) {
+        self.data.memory.fetch_sub(size, Ordering::SeqCst);
+    }
+}
+
+impl RpdoContext for Sparse {
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        loop {
+            if let Some(reg) = self.register(register) {
+                let mut reg_data = reg.lock();
+                let Some(reg_data) = reg_data.as_mut() else {
+                    return Err(Error::InvalidRegister);
+                };
+                let len = reg_data.len();
+                let new_len = len.max(usize::try_from(offset)? + data.len());
+                let grown = if self.register_flexible {
+                    new_len - len
+                } else {
+                    0
+                };
+                self.reserve(grown)?;
+                if let Err(e) = write_data(reg_data, offset, data, self.register_flexible) {
+                    self.release(grown);
+                    return Err(e);
+                }
+                return Ok(());
+            }
+            if !self.auto_create {
+                return Err(Error::InvalidRegister);
+            }
+            // the register is created only if the write succeeds
+            let mut reg_data = vec![0; self.register_size];
+            write_data(&mut reg_data, offset, data, self.register_flexible)?;
+            if self.insert(register, reg_data)? {
+                return Ok(());
+            }
+            // created concurrently, write to the existing register
+        }
+    }
+    fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> Result<Vec<u8>> {
+        let reg = self
+            .register(register)
+            .ok_or_else(|| Error::InvalidRegister)?;
+        let reg_data = reg.lock();
+        let Some(reg_data) = reg_data.as_ref() else {
+            return Err(Error::InvalidRegister);
+        };
+        read_data(reg_data, offset, data_size, self.register_flexible)
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt;
+use std::ops::RangeBounds;
+use std::sync::Arc;
+
+use super::{forward_context, range_bounds, RpdoContext};
+use crate::error::Error;
+use crate::Result;
+
+type CustomCheck = Arc<dyn Fn(u32, u32, &[u8]) -> Result<()> + Send + Sync>;
+
+/// Register value data type (little-endian)
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ValueType {
+    /// Unsigned 8-bit integer
+    U8,
+    /// Signed 8-bit integer
+    I8,
+    /// Unsigned 16-bit integer
+    U16,
+    /// Signed 16-bit integer
+    I16,
+    /// Unsigned 32-bit integer
+    U32,
+    /// Signed 32-bit integer
+    I32,
+    /// Unsigned 64-bit integer
+    U64,
+    /// Signed 64-bit integer
+    I64,
+    /// 32-bit floating point number
+    F32,
+    /// 64-bit floating point number
+    F64,
+}
+
+impl ValueType {
+    /// The value size in bytes
+    pub fn size(self) -> usize {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::U64 | Self::I64 | Self::F64 => 8,
+        }
+    }
+    #[allow(clippy::cast_precision_loss)]
+    fn decode(self, buf: &[u8]) -> f64 {
+        macro_rules! le {
+            ($t: ty) => {
+                <$t>::from_le_bytes(buf.try_into().unwrap())
+            };
+        }
+        match self {
+            Self::U8 => f64::from(buf[0]),
+            Self::I8 => f64::from(le!(i8)),
+            Self::U16 => f64::from(le!(u16)),
+            Self::I16 => f64::from(le!(i16)),
+            Self::U32 => f64::from(le!(u32)),
+            Self::I32 => f64::from(le!(i32)),
+            Self::U64 => le!(u64) as f64,
+            Self::I64 => le!(i64) as f64,
+            Self::F32 => f64::from(le!(f32)),
+            Self::F64 => le!(f64),
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Check {
+    Value {
+        offset: u32,
+        value_type: ValueType,
+        min: Option<f64>,
+        max: Option<f64>,
+        allowed: Option<Vec<f64>>,
+    },
+    Custom(CustomCheck),
+}
+
+/// A register write constraint
+///
+/// Value constraints check a typed value at a register offset. Writes which partially overlap the
+/// value are rejected, floating point values must be finite. Values are compared as `f64`.
+#[derive(Clone)]
+pub struct Constraint {
+    check: Check,
+}
+
+impl fmt::Debug for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.check {
+            Check::Value {
+                offset,
+                value_type,
+                min,
+                max,
+                ref allowed,
+            } => f
+                .debug_struct("Constraint")
+                .field("offset", &offset)
+                .field("value_type", &value_type)
+                .field("min", &min)
+                .field("max", &max)
+                .field("allowed", allowed)
+                .finish(),
+            Check::Custom(_) => f.write_str("Constraint(custom)"),
+        }
+    }
+}
+
+impl Constraint {
+    /// Create a new value constraint
+    pub fn value(offset: u32, value_type: ValueType) -> Self {
+        Self {
+            check: Check::Value {
+                offset,
+                value_type,
+                min: None,
+                max: None,
+                allowed: None,
+            },
+        }
+    }
+    /// Create a new custom constraint. The closure gets the register, the write offset and the
+    /// data and must return an error to reject the write
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(u32, u32, &[u8]) -> Result<()> + Send + Sync + 'static,
+    {
+        Self {
+            check: Check::Custom(Arc::new(f)),
+        }
+    }
+    /// Set the minimum allowed value
+    pub fn with_min(mut self, value: f64) -> Self {
+        if let Check::Value { ref mut min, .. } = self.check {
+            *min = Some(value);
+        }
+        self
+    }
+    /// Set the maximum allowed value
+    pub fn with_max(mut self, value: f64) -> Self {
+        if let Check::Value { ref mut max, .. } = self.check {
+            *max = Some(value);
+        }
+        self
+    }
+    /// Set the list of allowed values
+    pub fn with_allowed(mut self, values: impl IntoIterator<Item = f64>) -> Self {
+        if let Check::Value {
+            ref mut allowed, ..
+        } = self.check
+        {
+            *allowed = Some(values.into_iter().collect());
+        }
+        self
+    }
+    fn validate(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let (value_offset, value_type, min, max, allowed) = match self.check {
+            Check::Value {
+                offset,
+                value_type,
+                min,
+                max,
+                ref allowed,
+            } => (offset, value_type, min, max, allowed),
+            Check::Custom(ref f) => return f(register, offset, data),
+        };
+        let value_start = usize::try_from(value_offset)?;
+        let value_end = value_start + value_type.size();
+        let start = usize::try_from(offset)?;
+        let end = start + data.len();
+        if end <= value_start || start >= value_end {
+            return Ok(());
+        }
+        if start > value_start || end < value_end {
+            return Err(Error::validation(format!(
+                "register {} offset {}: partial write of a {:?} value",
+                register, value_offset, value_type
+            )));
+        }
+        let value = value_type.decode(&data[value_start - start..value_end - start]);
+        if !value.is_finite() {
+            return Err(Error::validation(format!(
+                "register {} offset {}: value is not finite",
+                register, value_offset
+            )));
+        }
+        if let Some(min) = min {
+            if value < min {
+                return Err(Error::validation(format!(
+                    "register {} offset {}: value {} is less than the minimum {}",
+                    register, value_offset, value, min
+                )));
+            }
+        }
+        if let Some(max) = max {
+            if value > max {
+                return Err(Error::validation(format!(
+                    "register {} offset {}: value {} is greater than the maximum {}",
+                    register, value_offset, value, max
+                )));
+            }
+        }
+        if let Some(allowed) = allowed {
+            #[allow(clippy::float_cmp)]
+            if !allowed.iter().any(|v| *v == value) {
+                return Err(Error::validation(format!(
+                    "register {} offset {}: value {} is not allowed",
+                    register, value_offset, value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct Rule {
+    first: u32,
+    last: u32,
+    constraint: Constraint,
+}
+
+/// A context wrapper which validates writes with register constraints
+///
+/// Rejected writes return [`Error::Validation`].
+#[derive(Clone)]
+pub struct Validated<CTX>
+where
+    CTX: RpdoContext,
+{
+    context: CTX,
+    rules: Vec<Rule>,
+}
+
+impl<CTX> Validated<CTX>
+where
+    CTX: RpdoContext,
+{
+    /// Create a new validated context
+    pub fn new(context: CTX) -> Self {
+        Self {
+            context,
+            rules: Vec::new(),
+        }
+    }
+    /// Add a constraint for the register range
+    pub fn with_constraint(
+        mut self,
+        registers: impl RangeBounds<u32>,
+        constraint: Constraint,
+    ) -> Result<Self> {
+        let (first, last) = range_bounds(&registers)?;
+        self.rules.push(Rule {
+            first,
+            last,
+            constraint,
+        });
+        Ok(self)
+    }
+    /// The inner context
+    pub fn context(&self) -> &CTX {
+        &self.context
+    }
+    /// Validate a write without applying it
+    pub fn validate(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        for rule in &self.rules {
+            if register >= rule.first && register <= rule.last {
+                rule.constraint.validate(register, offset, data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<CTX> RpdoContext for Validated<CTX>
+where
+    CTX: RpdoContext,
+{
+    forward_context!(context => get_bytes, get_bytes_with_metadata);
+    forward_context!(context => get_history, get_bytes_with_quality);
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.validate(register, offset, data)?;
+        self.context.set_bytes(register, offset, data)
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.validate(register, offset, data)?;
+        self.context.set_bytes_from(source, register, offset, data)
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::{forward_context, now_ns, RpdoContext};
+use crate::comm::RegisterMetadata;
+use crate::{Mutex, Result};
+
+/// A context wrapper which keeps a version counter, the last write timestamp and the writer's
+/// source id for each register
+///
+/// Local writes with [`RpdoContext::set_bytes`] are recorded with the source id zero, as well as
+/// remote writes of clients which have not set their source ids (see
+/// [`crate::io::SimpleClient::with_source_id`]).
+///
+/// Each register is locked separately, so writes and metadata reads of different registers do not
+/// block each other.
+pub struct Versioned<CTX>
+where
+    CTX: RpdoContext,
+{
+    context: CTX,
+    metadata: Arc<Mutex<BTreeMap<u32, Arc<Mutex<RegisterMetadata>>>>>,
+}
+
+impl<CTX> Clone for Versioned<CTX>
+where
+    CTX: RpdoContext + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl<CTX> Versioned<CTX>
+where
+    CTX: RpdoContext,
+{
+    /// Create a new versioned context
+    pub fn new(context: CTX) -> Self {
+        Self {
+            context,
+            metadata: <_>::default(),
+        }
+    }
+    /// The inner context
+    pub fn context(&self) -> &CTX {
+        &self.context
+    }
+    /// Get register metadata
+    pub fn metadata(&self, register: u32) -> RegisterMetadata {
+        let entry = self.metadata.lock().get(&register).cloned();
+        entry.map(|e| *e.lock()).unwrap_or_default()
+    }
+    /// Get register version
+    pub fn version(&self, register: u32) -> u64 {
+        self.metadata(register).version
+    }
+    /// Get or create the register metadata entry
+    fn entry(&self, register: u32) -> Arc<Mutex<RegisterMetadata>> {
+        self.metadata.lock().entry(register).or_default().clone()
+    }
+    /// Remove the entry if the register has never been written and the entry is not used by
+    /// other calls, so invalid register numbers do not make the map grow
+    fn release(&self, register: u32, entry: Arc<Mutex<RegisterMetadata>>) {
+        let mut metadata = self.metadata.lock();
+        // new entry references are taken only under the map lock
+        if Arc::strong_count(&entry) == 2 && entry.lock().version == 0 {
+            metadata.remove(&register);
+        }
+    }
+}
+
+impl<CTX> RpdoContext for Versioned<CTX>
+where
+    CTX: RpdoContext,
+{
+    forward_context!(context => get_bytes, get_history, get_bytes_with_quality);
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.set_bytes_from(0, register, offset, data)
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        let entry = self.entry(register);
+        let result = {
+            let mut metadata = entry.lock();
+            self.context
+                .set_bytes_from(source, register, offset, data)
+                .map(|()| {
+                    metadata.version += 1;
+                    metadata.timestamp = now_ns();
+                    metadata.source = source;
+                })
+        };
+        self.release(register, entry);
+        result
+    }
+    fn get_bytes_with_metadata(
+        &self,
+        register: u32,
+        offset: u32,
+        data_size: u32,
+        since_version: u64,
+    ) -> Result<(RegisterMetadata, Option<Vec<u8>>)> {
+        let entry = self.entry(register);
+        let result = {
+            let metadata = entry.lock();
+            if since_version > 0 && metadata.version <= since_version {
+                Ok((*metadata, None))
+            } else {
+                self.context
+                    .get_bytes(register, offset, data_size)
+                    .map(|data| (*metadata, Some(data)))
+            }
+        };
+        self.release(register, entry);
+        result
+    }
+}
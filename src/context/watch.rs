@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use rtsc::locking::{Condvar, RawMutex};
+
+use super::{forward_context, RpdoContext};
+use crate::{Mutex, Result};
+
+/// Write event channel receiver
+pub type WriteEventReceiver = rtsc::channel::Receiver<WriteEvent, RawMutex, Condvar>;
+
+type WriteEventSender = rtsc::channel::Sender<WriteEvent, RawMutex, Condvar>;
+
+/// A successful write notification
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct WriteEvent {
+    /// The register written
+    pub register: u32,
+    /// The offset within the register
+    pub offset: u32,
+    /// The written data length
+    pub len: u32,
+}
+
+enum Notifier {
+    Callback(Box<dyn Fn(WriteEvent) + Send + Sync>),
+    Channel(WriteEventSender),
+}
+
+struct Watcher {
+    notifier: Notifier,
+    changes_only: bool,
+}
+
+/// A context wrapper which notifies watchers after each successful write
+///
+/// If at least one watcher is interested in changes only, writes are serialized and the
+/// previous register data is compared with the new one.
+pub struct Watched<CTX>
+where
+    CTX: RpdoContext,
+{
+    context: CTX,
+    watchers: Arc<Mutex<Vec<Arc<Watcher>>>>,
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl<CTX> Clone for Watched<CTX>
+where
+    CTX: RpdoContext + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            context: self.context.clone(),
+            watchers: self.watchers.clone(),
+            write_lock: self.write_lock.clone(),
+        }
+    }
+}
+
+impl<CTX> Watched<CTX>
+where
+    CTX: RpdoContext,
+{
+    /// Create a new watched context
+    pub fn new(context: CTX) -> Self {
+        Self {
+            context,
+            watchers: <_>::default(),
+            write_lock: <_>::default(),
+        }
+    }
+    /// The inner context
+    pub fn context(&self) -> &CTX {
+        &self.context
+    }
+    /// Register a channel watcher. If the channel is full, events are dropped. The watcher is
+    /// removed automatically when the receiver is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the capacity is zero
+    pub fn watch(&self, capacity: usize, changes_only: bool) -> WriteEventReceiver {
+        let (tx, rx) = rtsc::channel::bounded(capacity);
+        self.watchers.lock().push(Arc::new(Watcher {
+            notifier: Notifier::Channel(tx),
+            changes_only,
+        }));
+        rx
+    }
+    /// Register a callback watcher. The callback is called in the writer's thread so it must not
+    /// block.
+    pub fn on_write<F>(&self, changes_only: bool, callback: F)
+    where
+        F: Fn(WriteEvent) + Send + Sync + 'static,
+    {
+        self.watchers.lock().push(Arc::new(Watcher {
+            notifier: Notifier::Callback(Box::new(callback)),
+            changes_only,
+        }));
+    }
+    fn write<F>(&self, register: u32, offset: u32, data: &[u8], f: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        let watchers = self.watchers.lock().clone();
+        if watchers.is_empty() {
+            return f();
+        }
+        let len = u32::try_from(data.len())?;
+        let changed = if data.is_empty() {
+            f()?;
+            false
+        } else if watchers.iter().any(|w| w.changes_only) {
+            let _lock = self.write_lock.lock();
+            let previous = self.context.get_bytes(register, offset, len).ok();
+            f()?;
+            previous.map_or(true, |p| p != data)
+        } else {
+            f()?;
+            true
+        };
+        self.notify(
+            &watchers,
+            WriteEvent {
+                register,
+                offset,
+                len,
+            },
+            changed,
+        );
+        Ok(())
+    }
+    fn notify(&self, watchers: &[Arc<Watcher>], event: WriteEvent, changed: bool) {
+        for watcher in watchers {
+            if watcher.changes_only && !changed {
+                continue;
+            }
+            match watcher.notifier {
+                Notifier::Callback(ref callback) => callback(event),
+                Notifier::Channel(ref tx) => match tx.try_send(event) {
+                    Ok(()) => {}
+                    Err(rtsc::Error::ChannelClosed) => {
+                        self.watchers.lock().retain(|w| !Arc::ptr_eq(w, watcher));
+                    }
+                    Err(e) => {
+                        tracing::warn!(register = event.register, error = %e, "write event dropped");
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<CTX> RpdoContext for Watched<CTX>
+where
+    CTX: RpdoContext,
+{
+    forward_context!(context => get_bytes, get_bytes_with_metadata);
+    forward_context!(context => get_history, get_bytes_with_quality);
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.write(register, offset, data, || {
+            self.context.set_bytes(register, offset, data)
+        })
+    }
+    fn set_bytes_from(&self, source: u32, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.write(register, offset, data, || {
+            self.context.set_bytes_from(source, register, offset, data)
+        })
+    }
+}
@@ -0,0 +1,581 @@
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use binrw::prelude::*;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, OsRng, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+
+use crate::comm::PacketHeader;
+use crate::error::Error;
+use crate::io::{PeerAddress, ReadTimeout};
+use crate::Result;
+
+/// The pre-shared key size
+pub const KEY_SIZE: usize = 32;
+
+/// The maximum accepted envelope size
+const MAX_ENVELOPE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The maximum datagram size
+const MAX_DATAGRAM_SIZE: usize = 65536;
+
+/// The maximum number of client sessions kept by the server side
+const MAX_PEER_SESSIONS: usize = 64;
+
+/// The envelope counter replay window size
+const REPLAY_WINDOW: u64 = 64;
+
+/// AEAD tag size
+const TAG_SIZE: usize = 16;
+
+/// Salt size
+const SALT_SIZE: usize = 15;
+
+/// Challenge size
+const CHALLENGE_SIZE: usize = 16;
+
+/// Envelope kind: session request (client to server), carries no data
+const KIND_HELLO: u8 = 0;
+/// Envelope kind: session challenge (server to client), carries the challenge
+const KIND_CHALLENGE: u8 = 1;
+/// Envelope kind: unknown session (server to client), carries no data
+const KIND_REJECT: u8 = 2;
+/// Envelope kind: packet
+const KIND_DATA: u8 = 3;
+
+/// The stream side, the nonces of each direction are separated by the side
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Side {
+    /// The side which sends requests
+    Client,
+    /// The side which replies to requests
+    Server,
+}
+
+impl Side {
+    fn code(self) -> u8 {
+        match self {
+            Side::Client => 0,
+            Side::Server => 1,
+        }
+    }
+}
+
+/// Envelope header, the whole header is authenticated as the associated data. The nonce is
+/// composed of the sender salt, the sender side and the sender envelope counter
+#[binrw]
+#[brw(little, magic = b"RE")]
+#[derive(Debug, Clone)]
+struct EnvelopeHeader {
+    kind: u8,
+    salt: [u8; SALT_SIZE],
+    side: u8,
+    counter: u64,
+    size: u32,
+}
+
+impl EnvelopeHeader {
+    const SIZE: usize = 31;
+
+    fn nonce(&self) -> XNonce {
+        let mut nonce = XNonce::default();
+        nonce[..SALT_SIZE].copy_from_slice(&self.salt);
+        nonce[SALT_SIZE] = self.side;
+        nonce[SALT_SIZE + 1..].copy_from_slice(&self.counter.to_le_bytes());
+        nonce
+    }
+}
+
+/// Envelope counter sliding window
+#[derive(Default)]
+struct ReplayWindow {
+    last: Option<u64>,
+    mask: u64,
+}
+
+impl ReplayWindow {
+    /// Returns false if the counter has been already seen or is too old
+    fn check(&mut self, counter: u64) -> bool {
+        let Some(last) = self.last else {
+            self.last = Some(counter);
+            self.mask = 1;
+            return true;
+        };
+        if counter > last {
+            let shift = counter - last;
+            self.mask = if shift < REPLAY_WINDOW {
+                (self.mask << shift) | 1
+            } else {
+                1
+            };
+            self.last = Some(counter);
+            return true;
+        }
+        let age = last - counter;
+        if age >= REPLAY_WINDOW || self.mask & (1 << age) != 0 {
+            return false;
+        }
+        self.mask |= 1 << age;
+        true
+    }
+}
+
+/// An opened envelope
+enum Opened {
+    Packet(Vec<u8>),
+    Skipped,
+    SessionRejected,
+}
+
+/// A client session kept by the server side
+struct PeerSession {
+    challenge: [u8; CHALLENGE_SIZE],
+    window: ReplayWindow,
+    seen: u64,
+}
+
+/// A stream wrapper which seals each packet into an authenticated encryption envelope
+/// (XChaCha20-Poly1305) with a pre-shared key. Works with both datagram ([`crate::io::UdpStream`])
+/// and stream transports.
+///
+/// Before the first packet, the client side requests a random challenge from the server side,
+/// all client packets are bound to the challenge and all server packets are bound to the random
+/// salt of the client, so packets recorded in other sessions (e.g. a previous TCP connection) are
+/// rejected. Replayed packets within a session are detected by envelope counters. On stream
+/// transports, packets which fail authentication or replay checks are rejected with
+/// [`Error::Authentication`]. Wrappers created with [`Encrypted::create_datagram`] drop such
+/// datagrams instead and keep reading, so a forged datagram does not interrupt other peers.
+///
+/// The server side keeps a limited number of client sessions. A client whose session has been
+/// forgotten (or which talks to a restarted server) gets a rejection, the pending call fails with
+/// [`Error::Authentication`] and the session is re-established on the next write. An attacker
+/// with network access can still make calls fail (e.g. by replaying a session request of a
+/// forgotten session), but can not make recorded packets accepted. The pre-shared key provides
+/// no forward secrecy.
+pub struct Encrypted<S> {
+    stream: S,
+    cipher: XChaCha20Poly1305,
+    side: Side,
+    salt: [u8; SALT_SIZE],
+    counter: u64,
+    write_buf: Vec<u8>,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    // client side: the server challenge and the server envelope counters
+    challenge: Option<[u8; CHALLENGE_SIZE]>,
+    server: Option<([u8; SALT_SIZE], ReplayWindow)>,
+    // server side: client sessions and the client the replies are sealed for
+    peers: HashMap<[u8; SALT_SIZE], PeerSession>,
+    peer_seq: u64,
+    reply_to: Option<[u8; SALT_SIZE]>,
+    datagram: bool,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<S> Encrypted<S>
+where
+    S: Read + Write,
+{
+    /// Wrap a stream, both sides must share the same key
+    pub fn create(stream: S, key: &[u8; KEY_SIZE], side: Side) -> Result<Self> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.try_fill_bytes(&mut salt).map_err(Error::failed)?;
+        Ok(Self {
+            stream,
+            cipher: XChaCha20Poly1305::new(key.into()),
+            side,
+            salt,
+            counter: 0,
+            write_buf: Vec::new(),
+            read_buf: Vec::new(),
+            read_pos: 0,
+            challenge: None,
+            server: None,
+            peers: HashMap::new(),
+            peer_seq: 0,
+            reply_to: None,
+            datagram: false,
+            dropped: <_>::default(),
+        })
+    }
+    /// Wrap a datagram stream (e.g. [`crate::io::UdpStream`]), each envelope must be received in
+    /// a single datagram. Datagrams which can not be parsed or fail authentication or replay
+    /// checks are dropped
+    pub fn create_datagram(stream: S, key: &[u8; KEY_SIZE], side: Side) -> Result<Self> {
+        Ok(Self {
+            datagram: true,
+            ..Self::create(stream, key, side)?
+        })
+    }
+    /// The number of dropped datagrams, the counter is shared and keeps updating after the
+    /// stream is moved (e.g. to a server processor)
+    pub fn dropped_datagrams(&self) -> Arc<AtomicU64> {
+        self.dropped.clone()
+    }
+    /// The inner stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+    /// The inner stream (mutable)
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+    /// Seal and send all complete packets from the write buffer
+    fn seal_pending(&mut self) -> Result<()> {
+        loop {
+            if self.write_buf.len() < PacketHeader::SIZE {
+                return Ok(());
+            }
+            let header = PacketHeader::read(&mut Cursor::new(&self.write_buf))?;
+            let packet_len = PacketHeader::SIZE + usize::try_from(header.size)?;
+            if self.write_buf.len() < packet_len {
+                return Ok(());
+            }
+            let binding = match self.side {
+                Side::Client => match self.challenge {
+                    Some(challenge) => challenge.to_vec(),
+                    None => self.handshake()?.to_vec(),
+                },
+                Side::Server => self
+                    .reply_to
+                    .ok_or_else(|| Error::failed("no authenticated peer to reply to"))?
+                    .to_vec(),
+            };
+            let packet = self.write_buf.drain(..packet_len).collect::<Vec<u8>>();
+            let envelope = self.seal(KIND_DATA, &packet, &binding)?;
+            self.stream.write_all(&envelope)?;
+        }
+    }
+    /// Request a challenge from the server side
+    fn handshake(&mut self) -> Result<[u8; CHALLENGE_SIZE]> {
+        let hello = self.seal(KIND_HELLO, &[], &[])?;
+        self.stream.write_all(&hello)?;
+        self.stream.flush()?;
+        loop {
+            let challenge = self.receive().and_then(|(header, header_buf, sealed)| {
+                if header.kind != KIND_CHALLENGE {
+                    // replies to the requests of the rejected session
+                    tracing::debug!(kind = header.kind, "envelope skipped during handshake");
+                    return Ok(None);
+                }
+                let challenge = self.open_from_server(&header, &header_buf, &sealed)?;
+                challenge
+                    .try_into()
+                    .map(Some)
+                    .map_err(|_| Error::InvalidData)
+            });
+            match challenge {
+                Ok(Some(challenge)) => {
+                    self.challenge = Some(challenge);
+                    return Ok(challenge);
+                }
+                Ok(None) => {}
+                Err(e) => self.drop_datagram(e)?,
+            }
+        }
+    }
+    /// Drop a datagram which can not be opened, other errors (and all errors of stream
+    /// transports) are returned
+    fn drop_datagram(&self, e: Error) -> Result<()> {
+        if !self.datagram || matches!(e, Error::Io(_)) {
+            return Err(e);
+        }
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(error = %e, "encrypted datagram dropped");
+        Ok(())
+    }
+    /// Seal an envelope, the binding is authenticated but not sent
+    fn seal(&mut self, kind: u8, data: &[u8], binding: &[u8]) -> Result<Vec<u8>> {
+        let header = EnvelopeHeader {
+            kind,
+            salt: self.salt,
+            side: self.side.code(),
+            counter: self.counter,
+            size: u32::try_from(data.len() + TAG_SIZE)?,
+        };
+        self.counter = self.counter.checked_add(1).ok_or(Error::Overflow)?;
+        let mut envelope = Cursor::new(Vec::with_capacity(
+            EnvelopeHeader::SIZE + data.len() + TAG_SIZE,
+        ));
+        header.write(&mut envelope)?;
+        let mut envelope = envelope.into_inner();
+        let mut aad = envelope.clone();
+        aad.extend(binding);
+        let sealed = self
+            .cipher
+            .encrypt(
+                &header.nonce(),
+                Payload {
+                    msg: data,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| Error::Authentication)?;
+        envelope.extend(sealed);
+        Ok(envelope)
+    }
+    /// Receive the next envelope, the data is read in chunks, so a forged size does not make
+    /// the whole buffer allocated
+    fn receive(&mut self) -> Result<(EnvelopeHeader, [u8; EnvelopeHeader::SIZE], Vec<u8>)> {
+        if self.datagram {
+            return self.receive_datagram();
+        }
+        let mut header_buf = [0u8; EnvelopeHeader::SIZE];
+        self.stream.read_exact(&mut header_buf)?;
+        let header = EnvelopeHeader::read(&mut Cursor::new(&header_buf))?;
+        let size = usize::try_from(header.size)?;
+        if size > MAX_ENVELOPE_SIZE {
+            return Err(Error::Overflow);
+        }
+        let mut sealed = Vec::new();
+        (&mut self.stream)
+            .take(u64::from(header.size))
+            .read_to_end(&mut sealed)?;
+        if sealed.len() < size {
+            return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+        }
+        // envelopes reflected back to the sender are rejected
+        if header.side == self.side.code() {
+            return Err(Error::Authentication);
+        }
+        Ok((header, header_buf, sealed))
+    }
+    /// Receive the next envelope from a single datagram, so a malformed datagram is consumed
+    /// entirely
+    fn receive_datagram(
+        &mut self,
+    ) -> Result<(EnvelopeHeader, [u8; EnvelopeHeader::SIZE], Vec<u8>)> {
+        let mut datagram = vec![0u8; MAX_DATAGRAM_SIZE];
+        let size = self.stream.read(&mut datagram)?;
+        datagram.truncate(size);
+        if size < EnvelopeHeader::SIZE {
+            return Err(Error::InvalidData);
+        }
+        let sealed = datagram.split_off(EnvelopeHeader::SIZE);
+        let header_buf: [u8; EnvelopeHeader::SIZE] = datagram.try_into().unwrap();
+        let header = EnvelopeHeader::read(&mut Cursor::new(&header_buf))?;
+        if usize::try_from(header.size)? != sealed.len() {
+            return Err(Error::InvalidData);
+        }
+        // envelopes reflected back to the sender are rejected
+        if header.side == self.side.code() {
+            return Err(Error::Authentication);
+        }
+        Ok((header, header_buf, sealed))
+    }
+    fn open(&self, header: &EnvelopeHeader, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+        self.cipher
+            .decrypt(&header.nonce(), Payload { msg: sealed, aad })
+            .map_err(|_| Error::Authentication)
+    }
+    /// Open a server envelope, bound to the client salt
+    fn open_from_server(
+        &mut self,
+        header: &EnvelopeHeader,
+        header_buf: &[u8],
+        sealed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut aad = header_buf.to_vec();
+        aad.extend(self.salt);
+        let data = self.open(header, &aad, sealed)?;
+        // a restarted server has a new salt and starts counting from zero
+        if self
+            .server
+            .as_ref()
+            .map_or(true, |(s, _)| *s != header.salt)
+        {
+            self.server = Some((header.salt, ReplayWindow::default()));
+        }
+        if !self.server.as_mut().unwrap().1.check(header.counter) {
+            return Err(Error::Authentication);
+        }
+        Ok(data)
+    }
+    /// Receive, authenticate and decrypt the next packet
+    fn open_next(&mut self) -> Result<()> {
+        loop {
+            let opened = self
+                .receive()
+                .and_then(|(header, header_buf, sealed)| match self.side {
+                    Side::Client => self.open_client(&header, &header_buf, &sealed),
+                    Side::Server => self.open_server(&header, &header_buf, &sealed),
+                });
+            match opened {
+                Ok(Opened::Packet(packet)) => {
+                    self.read_buf = packet;
+                    self.read_pos = 0;
+                    return Ok(());
+                }
+                Ok(Opened::Skipped) => {}
+                Ok(Opened::SessionRejected) => return Err(Error::Authentication),
+                Err(e) => self.drop_datagram(e)?,
+            }
+        }
+    }
+    fn open_client(
+        &mut self,
+        header: &EnvelopeHeader,
+        header_buf: &[u8],
+        sealed: &[u8],
+    ) -> Result<Opened> {
+        let data = self.open_from_server(header, header_buf, sealed)?;
+        match header.kind {
+            KIND_DATA => Ok(Opened::Packet(data)),
+            KIND_REJECT => {
+                self.challenge = None;
+                tracing::debug!("encrypted session rejected by the server");
+                Ok(Opened::SessionRejected)
+            }
+            // a late challenge to a repeated session request
+            KIND_CHALLENGE => Ok(Opened::Skipped),
+            _ => Err(Error::Authentication),
+        }
+    }
+    fn open_server(
+        &mut self,
+        header: &EnvelopeHeader,
+        header_buf: &[u8],
+        sealed: &[u8],
+    ) -> Result<Opened> {
+        match header.kind {
+            KIND_HELLO => {
+                self.open(header, header_buf, sealed)?;
+                self.peer_seq += 1;
+                let seen = self.peer_seq;
+                if let Some(peer) = self.peers.get_mut(&header.salt) {
+                    if !peer.window.check(header.counter) {
+                        return Err(Error::Authentication);
+                    }
+                    peer.seen = seen;
+                } else {
+                    if self.peers.len() >= MAX_PEER_SESSIONS {
+                        // forget the least recently active client session
+                        if let Some(oldest) = self
+                            .peers
+                            .iter()
+                            .min_by_key(|(_, p)| p.seen)
+                            .map(|(salt, _)| *salt)
+                        {
+                            self.peers.remove(&oldest);
+                        }
+                    }
+                    let mut window = ReplayWindow::default();
+                    window.check(header.counter);
+                    self.peers.insert(
+                        header.salt,
+                        PeerSession {
+                            challenge: [0; CHALLENGE_SIZE],
+                            window,
+                            seen,
+                        },
+                    );
+                }
+                let mut challenge = [0u8; CHALLENGE_SIZE];
+                OsRng
+                    .try_fill_bytes(&mut challenge)
+                    .map_err(Error::failed)?;
+                self.peers.get_mut(&header.salt).unwrap().challenge = challenge;
+                let envelope = self.seal(KIND_CHALLENGE, &challenge, &header.salt)?;
+                self.stream.write_all(&envelope)?;
+                self.stream.flush()?;
+                Ok(Opened::Skipped)
+            }
+            KIND_DATA => {
+                let Some(peer) = self.peers.get(&header.salt) else {
+                    let envelope = self.seal(KIND_REJECT, &[], &header.salt)?;
+                    self.stream.write_all(&envelope)?;
+                    self.stream.flush()?;
+                    tracing::debug!("encrypted packet of an unknown session rejected");
+                    return Ok(Opened::Skipped);
+                };
+                let mut aad = header_buf.to_vec();
+                aad.extend(peer.challenge);
+                let Ok(packet) = self.open(header, &aad, sealed) else {
+                    // the client may hold a challenge of a forgotten session
+                    let envelope = self.seal(KIND_REJECT, &[], &header.salt)?;
+                    self.stream.write_all(&envelope)?;
+                    self.stream.flush()?;
+                    return Err(Error::Authentication);
+                };
+                self.peer_seq += 1;
+                let peer = self.peers.get_mut(&header.salt).unwrap();
+                if !peer.window.check(header.counter) {
+                    return Err(Error::Authentication);
+                }
+                peer.seen = self.peer_seq;
+                if packet.len() < PacketHeader::SIZE {
+                    return Err(Error::InvalidData);
+                }
+                self.reply_to = Some(header.salt);
+                Ok(Opened::Packet(packet))
+            }
+            _ => Err(Error::Authentication),
+        }
+    }
+}
+
+impl<S> Read for Encrypted<S>
+where
+    S: Read + Write,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.read_pos >= self.read_buf.len() {
+            self.open_next().map_err(into_io_error)?;
+        }
+        let size = std::cmp::min(buf.len(), self.read_buf.len() - self.read_pos);
+        buf[..size].copy_from_slice(&self.read_buf[self.read_pos..self.read_pos + size]);
+        self.read_pos += size;
+        Ok(size)
+    }
+}
+
+impl<S> Write for Encrypted<S>
+where
+    S: Read + Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_buf.extend_from_slice(buf);
+        if let Err(e) = self.seal_pending() {
+            self.write_buf.clear();
+            return Err(into_io_error(e));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl<S> ReadTimeout for Encrypted<S>
+where
+    S: ReadTimeout,
+{
+    fn current_read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        self.stream.current_read_timeout()
+    }
+    fn apply_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.stream.apply_read_timeout(timeout)
+    }
+}
+
+impl<S> PeerAddress for Encrypted<S>
+where
+    S: PeerAddress,
+{
+    fn peer_address(&self) -> Option<SocketAddr> {
+        self.stream.peer_address()
+    }
+}
+
+fn into_io_error(e: Error) -> std::io::Error {
+    match e {
+        Error::Io(e) => e,
+        e => std::io::Error::other(e),
+    }
+}
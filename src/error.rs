@@ -20,10 +20,28 @@ pub const ERR_IO: u16 = 0x0008;
 pub const ERR_INVALID_DATA: u16 = 0x0009;
 /// Error code for failed data packing/unpacking
 pub const ERR_PACKER: u16 = 0x0010;
+/// Error code for rejected data validation
+pub const ERR_VALIDATION: u16 = 0x0011;
+/// Error code for a host in standby mode
+pub const ERR_STANDBY: u16 = 0x0012;
+/// Error code for a packet authentication failure
+pub const ERR_AUTHENTICATION: u16 = 0x0013;
+/// Error code for a timed out request
+pub const ERR_TIMEOUT: u16 = 0x0014;
 /// Error code for all other errors
 pub const ERR_FAILED: u16 = 0x0000;
+/// The first error code of the user-defined range (e.g. for custom command handlers)
+pub const ERR_USER_MIN: u16 = 0x8000;
+
+/// Marks the error payload as carrying [`ErrorDetails`]. The byte is never valid at the start of
+/// a UTF-8 message
+const DETAILS_MARKER: u8 = 0xFF;
 
 /// Error type
+///
+/// Error replies carry structured details ([`Error::Detailed`]) only to clients which have
+/// negotiated the protocol version 1 or above, use [`Error::kind`] to match such errors without
+/// the details, e.g. `matches!(e.kind(), Error::InvalidRegister)`
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     /// Host unknown
@@ -50,32 +68,175 @@ pub enum Error {
     UnsupportedVersion,
     /// I/O error
     #[error("I/O: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
     /// Invalid data
     #[error("Invalid data")]
     InvalidData,
     /// Packer/Unpacker error
     #[error("Packer: {0}")]
     Packer(#[from] binrw::Error),
+    /// Data validation failed
+    #[error("Validation: {0}")]
+    Validation(String),
+    /// The host is in standby mode
+    #[error("Host in standby")]
+    Standby,
+    /// Packet authentication failed (invalid key, tampered or replayed packet)
+    #[error("Authentication failed")]
+    Authentication,
+    /// Request timed out
+    #[error("Timed out")]
+    Timeout,
     /// Failed
     #[error("Failed: {0}")]
     Failed(String),
+    /// User-defined error, created with [`Error::try_custom`]
+    #[error("Error {0}: {1}")]
+    Custom(UserErrorCode, String),
+    /// An error with structured details
+    #[error("{inner} ({details})")]
+    Detailed {
+        /// The error
+        inner: Box<Error>,
+        /// The error details
+        details: ErrorDetails,
+    },
+}
+
+/// User-defined error code (`ERR_USER_MIN` and above)
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct UserErrorCode(u16);
+
+impl UserErrorCode {
+    /// Create a user-defined error code, `None` if the code is below `ERR_USER_MIN`
+    pub const fn new(code: u16) -> Option<Self> {
+        if code < ERR_USER_MIN {
+            None
+        } else {
+            Some(Self(code))
+        }
+    }
+    /// The error code
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for UserErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04X}", self.0)
+    }
+}
+
+/// Structured error details
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct ErrorDetails {
+    /// The register
+    pub register: Option<u32>,
+    /// The offset within the register
+    pub offset: Option<u32>,
+    /// The expected data size (e.g. the register size)
+    pub expected_size: Option<u32>,
+    /// The actual data size (e.g. the requested data end)
+    pub actual_size: Option<u32>,
+}
+
+impl ErrorDetails {
+    /// The encoded size (presence flags and four fields)
+    const SIZE: usize = 17;
+
+    fn fields(&self) -> [Option<u32>; 4] {
+        [
+            self.register,
+            self.offset,
+            self.expected_size,
+            self.actual_size,
+        ]
+    }
+    fn write_to(&self, buf: &mut Vec<u8>) {
+        let fields = self.fields();
+        let flags = fields
+            .iter()
+            .enumerate()
+            .fold(0u8, |flags, (i, f)| flags | (u8::from(f.is_some()) << i));
+        buf.push(flags);
+        for field in fields {
+            buf.extend_from_slice(&field.unwrap_or_default().to_le_bytes());
+        }
+    }
+    fn read_from(buf: &[u8]) -> Option<Self> {
+        let (&flags, buf) = buf.split_first()?;
+        if buf.len() < Self::SIZE - 1 {
+            return None;
+        }
+        let field = |i: usize| {
+            (flags & (1 << i) != 0)
+                .then(|| u32::from_le_bytes(buf[i * 4..i * 4 + 4].try_into().unwrap()))
+        };
+        Some(Self {
+            register: field(0),
+            offset: field(1),
+            expected_size: field(2),
+            actual_size: field(3),
+        })
+    }
+}
+
+impl fmt::Display for ErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = ["register", "offset", "expected size", "actual size"];
+        let mut first = true;
+        for (name, field) in names.iter().zip(self.fields()) {
+            if let Some(v) = field {
+                if !first {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: {}", name, v)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl From<Error> for Vec<u8> {
     fn from(err: Error) -> Self {
         let mut buf = Vec::<u8>::with_capacity(2);
         buf.extend_from_slice(&err.code().to_le_bytes());
+        let err = match err {
+            Error::Detailed { inner, details } => {
+                buf.push(DETAILS_MARKER);
+                details.write_to(&mut buf);
+                *inner
+            }
+            v => v,
+        };
         match err {
             Error::Io(e) => buf.extend_from_slice(e.to_string().as_bytes()),
             Error::Packer(e) => buf.extend_from_slice(e.to_string().as_bytes()),
-            Error::Failed(msg) => buf.extend_from_slice(msg.as_bytes()),
+            Error::Failed(msg) | Error::Validation(msg) | Error::Custom(_, msg) => {
+                buf.extend_from_slice(msg.as_bytes());
+            }
             _ => (),
         }
         buf
     }
 }
 
+/// Remove the structured details from an encoded error, for peers which have not negotiated them
+pub(crate) fn strip_details(payload: &mut Vec<u8>) {
+    if payload.get(2) == Some(&DETAILS_MARKER) && payload.len() >= 3 + ErrorDetails::SIZE {
+        payload.drain(2..3 + ErrorDetails::SIZE);
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        // stream wrappers may report protocol errors via I/O errors
+        e.downcast::<Error>().unwrap_or_else(Self::Io)
+    }
+}
+
 impl From<std::num::TryFromIntError> for Error {
     fn from(_: std::num::TryFromIntError) -> Self {
         Self::Overflow
@@ -88,37 +249,26 @@ impl From<&[u8]> for Error {
             return Error::Failed(String::new());
         }
         let code = u16::from_le_bytes(slice[..2].try_into().unwrap());
-        let msg = std::str::from_utf8(&slice[2..]).unwrap_or_default();
-        match code {
-            ERR_UNKNOWN_HOST => Self::UnknownHost,
-            ERR_INVALID_COMMAND => Self::InvalidCommand,
-            ERR_INVALID_REGISTER => Self::InvalidRegister,
-            ERR_INVALID_OFFSET => Self::InvalidOffset,
-            ERR_INVALID_REPLY => Self::InvalidReply,
-            ERR_OVERFLOW => Self::Overflow,
-            ERR_INVALID_VERSION => Self::UnsupportedVersion,
-            ERR_IO => Self::Io(std::io::Error::new(std::io::ErrorKind::Other, msg)),
-            ERR_INVALID_DATA => Self::InvalidData,
-            ERR_FAILED => Self::Failed(msg.to_string()),
-            _ => Self::Failed(format!("Unknown error code: 0x{:04X}", code)),
+        let mut payload = &slice[2..];
+        let mut details = None;
+        if payload.first() == Some(&DETAILS_MARKER) {
+            if let Some(d) = ErrorDetails::read_from(&payload[1..]) {
+                details = Some(d);
+                payload = &payload[1 + ErrorDetails::SIZE..];
+            }
+        }
+        let msg = std::str::from_utf8(payload).unwrap_or_default();
+        let err = Self::from_code(code, msg);
+        match details {
+            Some(details) => err.with_details(details),
+            None => err,
         }
     }
 }
 
 impl From<u16> for Error {
     fn from(e: u16) -> Self {
-        match e {
-            ERR_UNKNOWN_HOST => Self::UnknownHost,
-            ERR_INVALID_COMMAND => Self::InvalidCommand,
-            ERR_INVALID_REGISTER => Self::InvalidRegister,
-            ERR_INVALID_OFFSET => Self::InvalidOffset,
-            ERR_INVALID_REPLY => Self::InvalidReply,
-            ERR_OVERFLOW => Self::Overflow,
-            ERR_INVALID_VERSION => Self::UnsupportedVersion,
-            ERR_IO => Self::Io(std::io::Error::new(std::io::ErrorKind::Other, "I/O error")),
-            ERR_INVALID_DATA => Self::InvalidData,
-            _ => Self::Failed(format!("Unknown error code: 0x{:04X}", e)),
-        }
+        Self::from_code(e, "")
     }
 }
 
@@ -136,11 +286,98 @@ impl Error {
             Self::Io(_) => ERR_IO,
             Self::InvalidData => ERR_INVALID_DATA,
             Self::Packer(_) => ERR_PACKER,
+            Self::Validation(_) => ERR_VALIDATION,
+            Self::Standby => ERR_STANDBY,
+            Self::Authentication => ERR_AUTHENTICATION,
+            Self::Timeout => ERR_TIMEOUT,
             Self::Failed(_) => ERR_FAILED,
+            Self::Custom(code, _) => code.get(),
+            Self::Detailed { inner, .. } => inner.code(),
+        }
+    }
+    fn from_code(code: u16, msg: &str) -> Self {
+        match code {
+            ERR_UNKNOWN_HOST => Self::UnknownHost,
+            ERR_INVALID_COMMAND => Self::InvalidCommand,
+            ERR_INVALID_REGISTER => Self::InvalidRegister,
+            ERR_INVALID_OFFSET => Self::InvalidOffset,
+            ERR_INVALID_REPLY => Self::InvalidReply,
+            ERR_OVERFLOW => Self::Overflow,
+            ERR_INVALID_VERSION => Self::UnsupportedVersion,
+            ERR_IO => Self::Io(std::io::Error::other(if msg.is_empty() {
+                "I/O error"
+            } else {
+                msg
+            })),
+            ERR_INVALID_DATA => Self::InvalidData,
+            ERR_PACKER => Self::Packer(binrw::Error::Io(std::io::Error::other(msg))),
+            ERR_VALIDATION => Self::Validation(msg.to_string()),
+            ERR_STANDBY => Self::Standby,
+            ERR_AUTHENTICATION => Self::Authentication,
+            ERR_TIMEOUT => Self::Timeout,
+            ERR_FAILED => Self::Failed(msg.to_string()),
+            ERR_USER_MIN.. => Self::Custom(UserErrorCode(code), msg.to_string()),
+            _ => Self::Failed(format!("Unknown error code: 0x{:04X}", code)),
+        }
+    }
+    /// Create a user-defined error, codes below `ERR_USER_MIN` are rejected
+    pub fn try_custom<D: fmt::Display>(code: u16, msg: D) -> Result<Self, Self> {
+        let Some(code) = UserErrorCode::new(code) else {
+            return Err(Self::failed(format!(
+                "user-defined error codes start from 0x{:04X}, got 0x{:04X}",
+                ERR_USER_MIN, code
+            )));
+        };
+        Ok(Self::Custom(code, msg.to_string()))
+    }
+    /// Attach structured details, the fields set replace the existing ones
+    pub fn with_details(self, details: ErrorDetails) -> Self {
+        match self {
+            Self::Detailed {
+                inner,
+                details: current,
+            } => Self::Detailed {
+                inner,
+                details: ErrorDetails {
+                    register: details.register.or(current.register),
+                    offset: details.offset.or(current.offset),
+                    expected_size: details.expected_size.or(current.expected_size),
+                    actual_size: details.actual_size.or(current.actual_size),
+                },
+            },
+            v => Self::Detailed {
+                inner: Box::new(v),
+                details,
+            },
+        }
+    }
+    /// Attach the register number to the error details
+    pub fn with_register(self, register: u32) -> Self {
+        self.with_details(ErrorDetails {
+            register: Some(register),
+            ..ErrorDetails::default()
+        })
+    }
+    /// The structured details, if any
+    pub fn details(&self) -> Option<&ErrorDetails> {
+        match self {
+            Self::Detailed { details, .. } => Some(details),
+            _ => None,
+        }
+    }
+    /// The error without the structured details
+    pub fn kind(&self) -> &Error {
+        match self {
+            Self::Detailed { inner, .. } => inner.kind(),
+            v => v,
         }
     }
     /// Create a failed error
     pub fn failed<D: fmt::Display>(msg: D) -> Self {
         Self::Failed(msg.to_string())
     }
+    /// Create a validation error
+    pub fn validation<D: fmt::Display>(msg: D) -> Self {
+        Self::Validation(msg.to_string())
+    }
 }
@@ -1,11 +1,24 @@
 use binrw::prelude::*;
+use std::collections::BTreeMap;
 use std::io::Cursor;
+use std::marker::PhantomData;
 use std::sync::{atomic, Arc};
+use std::time::Instant;
 
-use crate::comm::{Command, Frame, RawDataHeader};
+use tracing::Level;
+
+use crate::comm::{
+    Command, CommandInfo, CommandList, DeadlineHeader, DiscoveryReply, Frame, Heartbeat, Hello,
+    HistoryReadHeader, HistoryReply, MetadataReadHeader, RawDataHeader, RegisterMetadata,
+    RegisterRange, CAP_COMPRESSION, COMMAND_CUSTOM_MIN, VERSION,
+};
+use crate::compression;
 use crate::context::RpdoContext;
-use crate::error::Error;
-use crate::Result;
+use crate::error::{Error, ErrorDetails};
+use crate::redundancy::Redundancy;
+use crate::trace::{dyn_event, Trace};
+use crate::watchdog::Watchdog;
+use crate::{Mutex, Result};
 
 /// Custom command handler
 pub trait CustomCommandHandler: Send + Sync + 'static {
@@ -13,6 +26,79 @@ pub trait CustomCommandHandler: Send + Sync + 'static {
     fn handle(&self, frame: &Frame, data: &[u8]) -> Result<Option<Vec<u8>>>;
 }
 
+impl<F> CustomCommandHandler for F
+where
+    F: Fn(&Frame, &[u8]) -> Result<Option<Vec<u8>>> + Send + Sync + 'static,
+{
+    fn handle(&self, frame: &Frame, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        self(frame, data)
+    }
+}
+
+/// A custom command handler with a typed request and reply (packed with binrw, little-endian)
+pub struct TypedCommandHandler<Req, Resp, F> {
+    f: F,
+    _types: PhantomData<fn(Req) -> Resp>,
+}
+
+impl<Req, Resp, F> TypedCommandHandler<Req, Resp, F>
+where
+    Req: for<'a> BinRead<Args<'a> = ()> + 'static,
+    Resp: for<'a> BinWrite<Args<'a> = ()> + 'static,
+    F: Fn(&Frame, Req) -> Result<Resp> + Send + Sync + 'static,
+{
+    /// Create a new typed handler
+    pub fn new(f: F) -> Self {
+        Self {
+            f,
+            _types: PhantomData,
+        }
+    }
+}
+
+impl<Req, Resp, F> CustomCommandHandler for TypedCommandHandler<Req, Resp, F>
+where
+    Req: for<'a> BinRead<Args<'a> = ()> + 'static,
+    Resp: for<'a> BinWrite<Args<'a> = ()> + 'static,
+    F: Fn(&Frame, Req) -> Result<Resp> + Send + Sync + 'static,
+{
+    fn handle(&self, frame: &Frame, data: &[u8]) -> Result<Option<Vec<u8>>> {
+        let request = Req::read_le(&mut Cursor::new(data))?;
+        let reply = (self.f)(frame, request)?;
+        let mut buf = Cursor::new(Vec::new());
+        reply.write_le(&mut buf)?;
+        Ok(Some(buf.into_inner()))
+    }
+}
+
+/// The result of frame processing
+pub type ProcessResult = Result<Option<(Frame, Vec<u8>)>>;
+
+/// Frame processing interceptor (middleware), e.g. for logging, metrics, access control or rate
+/// limiting
+///
+/// Interceptors see the requests enclosed into [`Command::Deadline`] envelopes, as if they were
+/// sent directly. Expired requests are dropped before the interceptors are called.
+pub trait Interceptor: Send + Sync + 'static {
+    /// Called before a frame is processed. An error short-circuits the processing and is sent as
+    /// the error reply (for frames which have no replies, the frame is dropped)
+    fn before(&self, frame: &Frame, data: &[u8]) -> Result<()> {
+        let _ = (frame, data);
+        Ok(())
+    }
+    /// Called after a frame is processed (including failed processing), the result may be
+    /// inspected, modified or dropped
+    fn after(&self, frame: &Frame, data: &[u8], result: &mut ProcessResult) {
+        let _ = (frame, data, result);
+    }
+}
+
+#[derive(Clone)]
+struct RegisteredCommand {
+    name: String,
+    handler: Arc<dyn CustomCommandHandler>,
+}
+
 /// Synchronous host
 #[allow(clippy::module_name_repetitions)]
 pub trait SyncHost {
@@ -36,6 +122,14 @@ where
     id: u32,
     inner: Arc<HostInner<CTX>>,
     custom_command_handler: Option<Arc<dyn CustomCommandHandler>>,
+    commands: Arc<BTreeMap<u16, RegisteredCommand>>,
+    interceptors: Arc<Vec<Arc<dyn Interceptor>>>,
+    trace: Trace,
+    redundancy: Option<Redundancy>,
+    info: Arc<DiscoveryReply>,
+    capabilities: u32,
+    sequence: Option<Arc<SequenceTracker>>,
+    watchdog: Option<Watchdog>,
 }
 
 impl<CTX> Host<CTX>
@@ -51,6 +145,22 @@ where
                 context,
             }),
             custom_command_handler: None,
+            commands: <_>::default(),
+            interceptors: <_>::default(),
+            trace: Trace::default(),
+            redundancy: None,
+            info: Arc::new(DiscoveryReply {
+                host_id: id,
+                version: VERSION,
+                ..DiscoveryReply::default()
+            }),
+            capabilities: if compression::SUPPORTED {
+                CAP_COMPRESSION
+            } else {
+                0
+            },
+            sequence: None,
+            watchdog: None,
         }
     }
     /// Set a custom command handler
@@ -61,6 +171,108 @@ where
         self.custom_command_handler = Some(custom_command_handler);
         self
     }
+    /// Set the level of per-frame tracing spans and events (default: DEBUG)
+    pub fn with_trace_level(mut self, level: Level) -> Self {
+        self.trace = Trace::new(level);
+        self
+    }
+    /// Append an interceptor to the chain. The `before` hooks are called in the order of
+    /// appending, the `after` hooks are called in the reverse order. If a `before` hook fails,
+    /// neither the following hooks nor any `after` hooks are called
+    pub fn with_interceptor(mut self, interceptor: Arc<dyn Interceptor>) -> Self {
+        Arc::make_mut(&mut self.interceptors).push(interceptor);
+        self
+    }
+    /// Register a handler for a custom command code (0x8000 and above). The registered handlers
+    /// take precedence over the custom command handler
+    pub fn try_with_command(
+        mut self,
+        code: u16,
+        name: &str,
+        handler: Arc<dyn CustomCommandHandler>,
+    ) -> Result<Self> {
+        if code < COMMAND_CUSTOM_MIN {
+            return Err(Error::InvalidCommand);
+        }
+        let commands = Arc::make_mut(&mut self.commands);
+        if commands.contains_key(&code) {
+            return Err(Error::failed(format!(
+                "command 0x{:04X} is already registered",
+                code
+            )));
+        }
+        commands.insert(
+            code,
+            RegisteredCommand {
+                name: name.to_owned(),
+                handler,
+            },
+        );
+        Ok(self)
+    }
+    /// Register a typed handler for a custom command code (0x8000 and above), the request and the
+    /// reply are packed with binrw (little-endian)
+    pub fn try_with_typed_command<Req, Resp, F>(self, code: u16, name: &str, f: F) -> Result<Self>
+    where
+        Req: for<'a> BinRead<Args<'a> = ()> + 'static,
+        Resp: for<'a> BinWrite<Args<'a> = ()> + 'static,
+        F: Fn(&Frame, Req) -> Result<Resp> + Send + Sync + 'static,
+    {
+        self.try_with_command(code, name, Arc::new(TypedCommandHandler::new(f)))
+    }
+    /// Set the device name and description, reported to discovery requests
+    pub fn with_info(mut self, name: &str, description: &str) -> Self {
+        let info = Arc::make_mut(&mut self.info);
+        info.name = name.to_owned();
+        info.description = description.to_owned();
+        self
+    }
+    /// Set the register summary, reported to discovery requests
+    pub fn with_register_summary(mut self, registers: Vec<RegisterRange>) -> Self {
+        Arc::make_mut(&mut self.info).registers = registers;
+        self
+    }
+    /// Set the capabilities (`CAP_*` bits) offered in protocol negotiation. Payload compression
+    /// is offered by default and only if the crate is built with the `compression` feature
+    pub fn with_capabilities(mut self, mut capabilities: u32) -> Self {
+        if !compression::SUPPORTED {
+            capabilities &= !CAP_COMPRESSION;
+        }
+        self.capabilities = capabilities;
+        self
+    }
+    /// Set a redundancy state, client writes are rejected if the host is in standby
+    pub fn with_redundancy(mut self, redundancy: Redundancy) -> Self {
+        self.redundancy = Some(redundancy);
+        self
+    }
+    /// Enable per-source frame id tracking for unconfirmed writes. Duplicate frames and frames up
+    /// to `window` ids behind the latest one of the same source are dropped, older frames are
+    /// considered as a source restart and accepted
+    ///
+    /// Only frames with an explicit source id are tracked (see
+    /// [`crate::io::SimpleClient::with_source_id`]), the source 0 is shared by all anonymous
+    /// clients and its frames are always accepted. The window of a source is reset by its protocol
+    /// negotiation request (hello), so restarted clients should negotiate before writing
+    pub fn with_sequence_window(mut self, window: u32) -> Self {
+        self.sequence = Some(Arc::new(SequenceTracker {
+            window: window.max(1),
+            last_ids: <_>::default(),
+            dropped: atomic::AtomicU64::new(0),
+        }));
+        self
+    }
+    /// Set a connection watchdog, client heartbeats are processed by it
+    pub fn with_watchdog(mut self, watchdog: Watchdog) -> Self {
+        self.watchdog = Some(watchdog);
+        self
+    }
+    /// The number of unconfirmed write frames dropped as duplicate or stale
+    pub fn dropped_frames(&self) -> u64 {
+        self.sequence
+            .as_ref()
+            .map_or(0, |s| s.dropped.load(atomic::Ordering::Relaxed))
+    }
 }
 
 impl<CTX> SyncHost for Host<CTX>
@@ -87,95 +299,324 @@ where
     }
 
     fn process_frame(&self, frame: &Frame, data: &[u8]) -> Result<Option<(Frame, Vec<u8>)>> {
+        let span = self.trace.frame_span(self.id, frame);
+        let _entered = span.enter();
+        let started = Instant::now();
+        let result = self.intercept(frame, data);
+        let latency_us = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        match result {
+            Ok(Some((ref reply, ref reply_data))) if reply.command == Command::Error => {
+                dyn_event!(
+                    self.trace.level(),
+                    latency_us,
+                    error = %Error::from(reply_data.as_slice()),
+                    "error reply"
+                );
+            }
+            Ok(Some(_)) => dyn_event!(self.trace.level(), latency_us, "reply"),
+            Ok(None) => dyn_event!(self.trace.level(), latency_us, "no reply"),
+            Err(ref e) => tracing::warn!(latency_us, error = %e, "frame processing failed"),
+        }
+        result
+    }
+}
+
+impl<CTX> Host<CTX>
+where
+    CTX: RpdoContext,
+{
+    fn intercept(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let enclosed;
+        let (frame, data) = if frame.command == Command::Deadline {
+            let header = DeadlineHeader::read(&mut Cursor::new(data))?;
+            if header.command == Command::Deadline {
+                return Err(Error::InvalidData);
+            }
+            if header.is_expired() {
+                tracing::debug!(
+                    source = frame.source,
+                    id = frame.id,
+                    "expired request dropped"
+                );
+                return Ok(None);
+            }
+            enclosed = Frame {
+                command: header.command,
+                ..frame.clone()
+            };
+            (&enclosed, &data[DeadlineHeader::SIZE..])
+        } else {
+            (frame, data)
+        };
+        for interceptor in self.interceptors.iter() {
+            if let Err(e) = interceptor.before(frame, data) {
+                if matches!(
+                    frame.command,
+                    Command::Reply | Command::Error | Command::WriteSharedContextUnconfirmed
+                ) {
+                    return Ok(None);
+                }
+                return self.reply(frame, Err(e));
+            }
+        }
+        let mut result = self.process(frame, data);
+        for interceptor in self.interceptors.iter().rev() {
+            interceptor.after(frame, data, &mut result);
+        }
+        result
+    }
+    fn process(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
         match frame.command {
             Command::Reply => {
                 return Ok(None);
             }
             Command::Error => {
                 let err: Error = Error::from(data);
-                eprintln!("host: {} error: {:?}", self.id, err);
+                tracing::warn!(host = self.id, source = frame.source, error = %err, "error frame received");
                 return Ok(None);
             }
             _ => {}
         }
         if !self.host_id_matches(frame) {
-            return Ok(Some((
-                self.create_frame(frame.source, frame.id, Command::Error),
-                Error::UnknownHost.into(),
-            )));
+            return self.reply(frame, Err(Error::UnknownHost));
         }
         match frame.command {
-            Command::Ping => Ok(Some((
-                self.create_frame(frame.source, frame.id, Command::Reply),
-                vec![],
-            ))),
-            Command::ReadSharedContext => {
-                let mut cursor = Cursor::new(data);
-                let raw_data_header = RawDataHeader::read(&mut cursor)?;
-                match self.inner.context.get_bytes(
-                    raw_data_header.register,
-                    raw_data_header.offset,
-                    raw_data_header.size,
-                ) {
-                    Ok(v) => Ok(Some((
-                        self.create_frame(frame.source, frame.id, Command::Reply),
-                        v,
-                    ))),
-                    Err(e) => Ok(Some((
-                        self.create_frame(frame.source, frame.id, Command::Error),
-                        e.into(),
-                    ))),
-                }
-            }
+            Command::Ping => self.reply(frame, Ok(vec![])),
+            Command::ReadSharedContext => self.read_shared_context(frame, data),
+            Command::ReadSharedContextMetadata => self.read_metadata(frame, data),
+            Command::ReadSharedContextQuality => self.read_quality(frame, data),
+            Command::ReadHistory => self.read_history(frame, data),
+            Command::Hello => self.hello(frame, data),
+            Command::Discover => self.reply(frame, pack(&*self.info)),
+            Command::RedundancyStatus => self.redundancy_status(frame),
+            Command::Heartbeat => self.heartbeat(frame, data),
             Command::WriteSharedContext | Command::WriteSharedContextUnconfirmed => {
-                let mut cursor = Cursor::new(data);
-                let raw_data_header = RawDataHeader::read(&mut cursor)?;
-                let raw_data = &data[RawDataHeader::SIZE..];
-                if raw_data_header.size != u32::try_from(raw_data.len())? {
-                    return Err(Error::InvalidData);
-                }
-                match self.inner.context.set_bytes(
-                    raw_data_header.register,
-                    raw_data_header.offset,
-                    raw_data,
-                ) {
-                    Ok(()) => {
-                        if frame.command == Command::WriteSharedContext {
-                            Ok(Some((
-                                self.create_frame(frame.source, frame.id, Command::Reply),
-                                vec![],
-                            )))
-                        } else {
-                            Ok(None)
-                        }
-                    }
-                    Err(e) => Ok(Some((
-                        self.create_frame(frame.source, frame.id, Command::Error),
-                        e.into(),
-                    ))),
-                }
+                self.write_shared_context(frame, data)
             }
-            _ => {
-                if let Some(ref custom_command_handler) = self.custom_command_handler {
-                    match custom_command_handler.handle(frame, data) {
-                        Ok(Some(v)) => Ok(Some((
-                            self.create_frame(frame.source, frame.id, Command::Reply),
-                            v,
-                        ))),
-                        Ok(None) => Ok(None),
-                        Err(e) => Ok(Some((
-                            self.create_frame(frame.source, frame.id, Command::Error),
-                            e.into(),
-                        ))),
-                    }
-                } else {
-                    Ok(Some((
-                        self.create_frame(frame.source, frame.id, Command::Error),
-                        Error::InvalidCommand.into(),
-                    )))
+            Command::ListCommands => self.list_commands(frame),
+            _ => self.custom_command(frame, data),
+        }
+    }
+    /// Reply with the data or with an error frame
+    fn reply(&self, frame: &Frame, result: Result<Vec<u8>>) -> ProcessResult {
+        Ok(Some(match result {
+            Ok(data) => (
+                self.create_frame(frame.source, frame.id, Command::Reply),
+                data,
+            ),
+            Err(e) => (
+                self.create_frame(frame.source, frame.id, Command::Error),
+                e.into(),
+            ),
+        }))
+    }
+    fn read_shared_context(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let header = RawDataHeader::read(&mut Cursor::new(data))?;
+        let result = self
+            .inner
+            .context
+            .get_bytes(header.register, header.offset, header.size)
+            .map_err(|e| request_details(e, header.register, header.offset, header.size));
+        self.reply(frame, result)
+    }
+    fn read_metadata(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let header = MetadataReadHeader::read(&mut Cursor::new(data))?;
+        let result = self
+            .inner
+            .context
+            .get_bytes_with_metadata(
+                header.register,
+                header.offset,
+                header.size,
+                header.since_version,
+            )
+            .map_err(|e| request_details(e, header.register, header.offset, header.size))
+            .and_then(|(metadata, v)| {
+                let mut buf = Cursor::new(Vec::with_capacity(
+                    RegisterMetadata::SIZE + v.as_ref().map_or(0, Vec::len),
+                ));
+                metadata.write(&mut buf)?;
+                if let Some(v) = v {
+                    buf.get_mut().extend(v);
                 }
+                Ok(buf.into_inner())
+            });
+        self.reply(frame, result)
+    }
+    fn read_quality(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let header = RawDataHeader::read(&mut Cursor::new(data))?;
+        let result = self
+            .inner
+            .context
+            .get_bytes_with_quality(header.register, header.offset, header.size)
+            .map_err(|e| request_details(e, header.register, header.offset, header.size))
+            .map(|(quality, v)| {
+                let mut buf = Vec::with_capacity(v.len() + 1);
+                buf.push(quality as u8);
+                buf.extend(v);
+                buf
+            });
+        self.reply(frame, result)
+    }
+    fn read_history(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let header = HistoryReadHeader::read(&mut Cursor::new(data))?;
+        let result = self
+            .inner
+            .context
+            .get_history(header.register, header.from, header.to, header.max_samples)
+            .map_err(|e| request_details(e, header.register, 0, 0))
+            .and_then(|samples| pack(&HistoryReply::new(samples)));
+        self.reply(frame, result)
+    }
+    fn hello(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let hello = Hello::read(&mut Cursor::new(data))?;
+        if let Some(ref sequence) = self.sequence {
+            sequence.reset(frame.source);
+        }
+        let result = hello
+            .negotiate(self.capabilities)
+            .and_then(|session| pack(&session));
+        self.reply(frame, result)
+    }
+    fn redundancy_status(&self, frame: &Frame) -> ProcessResult {
+        let Some(ref redundancy) = self.redundancy else {
+            return self.reply(frame, Err(Error::InvalidCommand));
+        };
+        self.reply(frame, pack(&redundancy.status()))
+    }
+    fn heartbeat(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let Some(ref watchdog) = self.watchdog else {
+            return self.reply(frame, Err(Error::InvalidCommand));
+        };
+        let heartbeat = Heartbeat::read(&mut Cursor::new(data))?;
+        let result = watchdog.heartbeat(frame.source, heartbeat.interval());
+        self.reply(frame, result.map(|()| vec![]))
+    }
+    fn write_shared_context(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let confirmed = frame.command == Command::WriteSharedContext;
+        if self.redundancy.as_ref().is_some_and(|r| !r.is_primary()) {
+            if confirmed {
+                return self.reply(frame, Err(Error::Standby));
             }
+            return Ok(None);
+        }
+        let header = RawDataHeader::read(&mut Cursor::new(data))?;
+        let raw_data = &data[RawDataHeader::SIZE..];
+        if header.size != u32::try_from(raw_data.len())? {
+            return Err(Error::InvalidData);
+        }
+        // malformed frames must not move the sequence window
+        if !confirmed
+            && self
+                .sequence
+                .as_ref()
+                .is_some_and(|s| !s.accept(frame.source, frame.id))
+        {
+            return Ok(None);
+        }
+        let result = self.inner.context.set_bytes_from(
+            frame.source,
+            header.register,
+            header.offset,
+            raw_data,
+        );
+        let result =
+            result.map_err(|e| request_details(e, header.register, header.offset, header.size));
+        if confirmed || result.is_err() {
+            self.reply(frame, result.map(|()| vec![]))
+        } else {
+            Ok(None)
+        }
+    }
+    fn list_commands(&self, frame: &Frame) -> ProcessResult {
+        let list = CommandList::new(
+            self.commands
+                .iter()
+                .map(|(code, c)| CommandInfo::new(*code, &c.name))
+                .collect(),
+        );
+        self.reply(frame, pack(&list))
+    }
+    fn custom_command(&self, frame: &Frame, data: &[u8]) -> ProcessResult {
+        let handler = self
+            .commands
+            .get(&frame.command.code())
+            .map(|c| &c.handler)
+            .or(self.custom_command_handler.as_ref());
+        let Some(handler) = handler else {
+            return self.reply(frame, Err(Error::InvalidCommand));
+        };
+        match handler.handle(frame, data) {
+            Ok(Some(v)) => self.reply(frame, Ok(v)),
+            Ok(None) => Ok(None),
+            Err(e) => self.reply(frame, Err(e)),
+        }
+    }
+}
+
+/// Attach the request to register errors, the details set by the context are kept
+fn request_details(e: Error, register: u32, offset: u32, size: u32) -> Error {
+    let request = match e.kind() {
+        Error::InvalidRegister => ErrorDetails {
+            register: Some(register),
+            ..ErrorDetails::default()
+        },
+        Error::InvalidOffset => ErrorDetails {
+            register: Some(register),
+            offset: Some(offset),
+            actual_size: offset.checked_add(size),
+            ..ErrorDetails::default()
+        },
+        _ => return e,
+    };
+    let current = e.details().copied().unwrap_or_default();
+    e.with_details(request).with_details(current)
+}
+
+/// Pack a reply structure
+fn pack<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: for<'a> BinWrite<Args<'a> = ()>,
+{
+    let mut buf = Cursor::new(Vec::new());
+    value.write_le(&mut buf)?;
+    Ok(buf.into_inner())
+}
+
+/// Per-source frame id tracking
+struct SequenceTracker {
+    window: u32,
+    last_ids: Mutex<BTreeMap<u32, u32>>,
+    dropped: atomic::AtomicU64,
+}
+
+impl SequenceTracker {
+    /// Returns false if the frame is a duplicate or stale, anonymous frames are always accepted
+    fn accept(&self, source: u32, id: u32) -> bool {
+        if source == 0 {
+            return true;
+        }
+        let mut last_ids = self.last_ids.lock();
+        let Some(last) = last_ids.get_mut(&source) else {
+            last_ids.insert(source, id);
+            return true;
+        };
+        // frame ids wrap around
+        let ahead = id.wrapping_sub(*last);
+        if ahead != 0 && ahead < 1 << 31 {
+            *last = id;
+            return true;
+        }
+        if last.wrapping_sub(id) < self.window {
+            self.dropped.fetch_add(1, atomic::Ordering::Relaxed);
+            return false;
         }
+        *last = id;
+        true
+    }
+    /// Forget the latest frame id of the source
+    fn reset(&self, source: u32) {
+        self.last_ids.lock().remove(&source);
     }
 }
 
@@ -186,3 +627,122 @@ where
     next_frame_id: atomic::AtomicU32,
     context: CTX,
 }
+
+/// A dispatcher which serves multiple virtual hosts on a single server endpoint, the host is
+/// picked by the frame target
+///
+/// Frames with the target 0 are processed by the default host, if set.
+pub struct MultiHost<H>
+where
+    H: SyncHost,
+{
+    hosts: Arc<BTreeMap<u32, H>>,
+    default_host: Option<u32>,
+    next_frame_id: Arc<atomic::AtomicU32>,
+}
+
+impl<H> Clone for MultiHost<H>
+where
+    H: SyncHost,
+{
+    fn clone(&self) -> Self {
+        Self {
+            hosts: self.hosts.clone(),
+            default_host: self.default_host,
+            next_frame_id: self.next_frame_id.clone(),
+        }
+    }
+}
+
+impl<H> Default for MultiHost<H>
+where
+    H: SyncHost,
+{
+    fn default() -> Self {
+        Self {
+            hosts: <_>::default(),
+            default_host: None,
+            next_frame_id: <_>::default(),
+        }
+    }
+}
+
+impl<H> MultiHost<H>
+where
+    H: SyncHost,
+{
+    /// Create a new multi-host dispatcher
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add a virtual host, the id must be non-zero and match the host's own one
+    pub fn try_with_host(mut self, id: u32, host: H) -> Result<Self>
+    where
+        H: Clone,
+    {
+        let frame = Frame {
+            source: 0,
+            target: id,
+            id: 0,
+            in_reply_to: 0,
+            command: Command::Ping,
+        };
+        if id == 0 || !host.host_id_matches(&frame) {
+            return Err(Error::failed(format!("the host id does not match {}", id)));
+        }
+        Arc::make_mut(&mut self.hosts).insert(id, host);
+        Ok(self)
+    }
+    /// Set the host which processes frames with the target 0
+    pub fn with_default_host(mut self, id: u32) -> Self {
+        self.default_host = Some(id);
+        self
+    }
+    /// Get a virtual host
+    pub fn host(&self, id: u32) -> Option<&H> {
+        self.hosts.get(&id)
+    }
+    fn resolve(&self, target: u32) -> Option<&H> {
+        if target == 0 {
+            self.default_host.and_then(|id| self.hosts.get(&id))
+        } else {
+            self.hosts.get(&target)
+        }
+    }
+}
+
+impl<H> SyncHost for MultiHost<H>
+where
+    H: SyncHost,
+{
+    type Context = H::Context;
+
+    fn host_id_matches(&self, frame: &Frame) -> bool {
+        self.resolve(frame.target).is_some()
+    }
+
+    fn create_frame(&self, target: u32, in_reply_to: u32, command: Command) -> Frame {
+        if let Some(host) = self.resolve(0) {
+            return host.create_frame(target, in_reply_to, command);
+        }
+        Frame {
+            source: 0,
+            target,
+            id: self.next_frame_id.fetch_add(1, atomic::Ordering::Relaxed),
+            in_reply_to,
+            command,
+        }
+    }
+
+    fn process_frame(&self, frame: &Frame, data: &[u8]) -> Result<Option<(Frame, Vec<u8>)>> {
+        if let Some(host) = self.resolve(frame.target) {
+            return host.process_frame(frame, data);
+        }
+        if matches!(frame.command, Command::Reply | Command::Error) {
+            return Ok(None);
+        }
+        let mut reply = self.create_frame(frame.source, frame.id, Command::Error);
+        reply.source = frame.target;
+        Ok(Some((reply, Error::UnknownHost.into())))
+    }
+}
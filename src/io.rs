@@ -1,18 +1,33 @@
-use crate::comm::{Command, Frame, Packet, RawDataHeader};
+use crate::comm::{
+    Command, CommandInfo, CommandList, DeadlineHeader, DiscoveryReply, Frame, Heartbeat, Hello,
+    HistoryReadHeader, HistoryReply, HistorySample, MetadataReadHeader, Packet, Quality,
+    RawDataHeader, RegisterMetadata, Session, CAP_COMPRESSION, MIN_VERSION, VERSION,
+};
+use crate::compression;
 use crate::context::RpdoContext;
-use crate::error::Error;
+use crate::error::{self, Error};
 use crate::host::SyncHost;
+use crate::redundancy::RedundancyStatus;
+use crate::trace::{dyn_event, Trace};
 use crate::Result;
 use binrw::prelude::*;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::{Cursor, Read, Write};
 use std::mem;
-use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
-use std::time::Duration;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic;
+use std::time::{Duration, Instant, SystemTime};
 
 const MAX_UDP_PACKET_SIZE: usize = 16384;
 
 const DEFAULT_ZERO_COPY_AFTER: usize = 32768;
 
+/// The maximum number of peer sessions kept by a server processor on a datagram transport
+const MAX_PEER_SESSIONS: usize = 1024;
+
+static NEXT_CONNECTION_ID: atomic::AtomicU64 = atomic::AtomicU64::new(1);
+
 /// A helper which wraps a UDP socket into a Read/Write stream
 pub struct UdpStream {
     socket: UdpSocket,
@@ -101,6 +116,10 @@ impl UdpStream {
         self.mtu = max_packet_size;
         Ok(self)
     }
+    /// The local socket address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr().map_err(Into::into)
+    }
     /// Set the peer address
     pub fn set_peer(&mut self, peer: impl ToSocketAddrs) -> Result<()> {
         let peer = peer
@@ -156,6 +175,404 @@ impl Write for UdpStream {
     }
 }
 
+/// A publisher which periodically sends register snapshots with
+/// [`Command::WriteSharedContextUnconfirmed`] frames over UDP unicast, broadcast or multicast
+///
+/// Multiple frames are packed into a single datagram while they fit the MTU.
+pub struct UdpPublisher {
+    socket: UdpSocket,
+    destination: SocketAddr,
+    source_id: u32,
+    target_id: u32,
+    registers: Vec<RawDataHeader>,
+    frame_id: u32,
+    mtu: usize,
+    buf: Vec<u8>,
+}
+
+impl UdpPublisher {
+    /// Create a new publisher
+    pub fn create(bind: impl ToSocketAddrs, destination: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind(bind)?;
+        let destination =
+            destination
+                .to_socket_addrs()?
+                .next()
+                .ok_or(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Invalid destination address",
+                )))?;
+        Ok(Self {
+            socket,
+            destination,
+            source_id: 0,
+            target_id: 0,
+            registers: Vec::new(),
+            frame_id: 0,
+            mtu: MAX_UDP_PACKET_SIZE,
+            buf: Vec::new(),
+        })
+    }
+    /// Set the frame source id (the publishing host id)
+    pub fn with_source_id(mut self, source_id: u32) -> Self {
+        self.source_id = source_id;
+        self
+    }
+    /// Set the frame target id (default: 0, all hosts)
+    pub fn with_target_id(mut self, target_id: u32) -> Self {
+        self.target_id = target_id;
+        self
+    }
+    /// Allow sending to broadcast addresses
+    pub fn with_broadcast(self, broadcast: bool) -> Result<Self> {
+        self.socket.set_broadcast(broadcast)?;
+        Ok(self)
+    }
+    /// Set multicast TTL
+    pub fn with_multicast_ttl(self, ttl: u32) -> Result<Self> {
+        self.socket.set_multicast_ttl_v4(ttl)?;
+        Ok(self)
+    }
+    /// Set the outgoing multicast interface
+    pub fn with_multicast_interface(self, interface: Ipv4Addr) -> Result<Self> {
+        socket2::SockRef::from(&self.socket).set_multicast_if_v4(&interface)?;
+        Ok(self)
+    }
+    /// Set if multicast datagrams are looped back to the local host
+    pub fn with_multicast_loop(self, multicast_loop: bool) -> Result<Self> {
+        self.socket.set_multicast_loop_v4(multicast_loop)?;
+        Ok(self)
+    }
+    /// Set the maximum datagram size
+    pub fn try_with_mtu(mut self, max_packet_size: usize) -> Result<Self> {
+        if max_packet_size > MAX_UDP_PACKET_SIZE {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "MTU too large",
+            )));
+        }
+        self.mtu = max_packet_size;
+        Ok(self)
+    }
+    /// Add a register to publish, zero size publishes the whole register
+    pub fn with_register(mut self, register: u32, offset: u32, size: u32) -> Self {
+        self.registers.push(RawDataHeader {
+            register,
+            offset,
+            size,
+        });
+        self
+    }
+    /// Publish the current snapshot of the registers
+    pub fn publish<C>(&mut self, context: &C) -> Result<()>
+    where
+        C: RpdoContext,
+    {
+        self.buf.clear();
+        for i in 0..self.registers.len() {
+            let header = &self.registers[i];
+            let data = context.get_bytes(header.register, header.offset, header.size)?;
+            let raw_data_header = RawDataHeader {
+                register: header.register,
+                offset: header.offset,
+                size: u32::try_from(data.len())?,
+            };
+            let frame = Frame {
+                source: self.source_id,
+                target: self.target_id,
+                id: self.frame_id,
+                in_reply_to: 0,
+                command: Command::WriteSharedContextUnconfirmed,
+            };
+            self.frame_id = self.frame_id.wrapping_add(1);
+            let packet = Packet::new(frame, RawDataHeader::SIZE + data.len());
+            if packet.size_full() > self.mtu {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Data too large",
+                )));
+            }
+            if self.buf.len() + packet.size_full() > self.mtu {
+                self.socket.send_to(&self.buf, self.destination)?;
+                self.buf.clear();
+            }
+            let mut c = Cursor::new(&mut self.buf);
+            c.set_position(c.get_ref().len() as u64);
+            packet.write_to(&mut c)?;
+            raw_data_header.write(&mut c)?;
+            self.buf.extend(data);
+        }
+        if !self.buf.is_empty() {
+            self.socket.send_to(&self.buf, self.destination)?;
+        }
+        Ok(())
+    }
+    /// Publish the snapshot with the given interval, returns on the first error
+    pub fn run<C>(&mut self, context: &C, interval: Duration) -> Result<()>
+    where
+        C: RpdoContext,
+    {
+        for _ in rtsc::time::interval(interval) {
+            self.publish(context)?;
+        }
+        Ok(())
+    }
+}
+
+/// A subscriber which applies [`Command::WriteSharedContextUnconfirmed`] frames received over UDP
+/// to a local context, without replying
+pub struct UdpSubscriber {
+    socket: UdpSocket,
+    source_id: Option<u32>,
+    host_id: Option<u32>,
+    buf: Vec<u8>,
+}
+
+impl UdpSubscriber {
+    /// Create a new subscriber
+    pub fn create(bind: impl ToSocketAddrs) -> Result<Self> {
+        let socket = UdpSocket::bind(bind)?;
+        Ok(Self {
+            socket,
+            source_id: None,
+            host_id: None,
+            buf: vec![0; MAX_UDP_PACKET_SIZE],
+        })
+    }
+    /// Join a multicast group on the given interface (use [`Ipv4Addr::UNSPECIFIED`] for the
+    /// default one)
+    pub fn join_multicast(self, group: Ipv4Addr, interface: Ipv4Addr) -> Result<Self> {
+        self.socket.join_multicast_v4(&group, &interface)?;
+        Ok(self)
+    }
+    /// Accept frames from the given source id only
+    pub fn with_source_id(mut self, source_id: u32) -> Self {
+        self.source_id = Some(source_id);
+        self
+    }
+    /// Accept frames with the given target id or 0 only
+    pub fn with_host_id(mut self, host_id: u32) -> Self {
+        self.host_id = Some(host_id);
+        self
+    }
+    /// Set read timeout
+    pub fn with_read_timeout(self, timeout: Duration) -> Result<Self> {
+        self.socket.set_read_timeout(Some(timeout))?;
+        Ok(self)
+    }
+    /// The local socket address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.socket.local_addr().map_err(Into::into)
+    }
+    /// Receive the next datagram and apply its frames to the context. Returns the number of
+    /// applied writes
+    ///
+    /// The whole datagram is validated first, so a malformed one is rejected with no frames
+    /// applied. Writes refused by the context (e.g. to invalid registers) are skipped and reported
+    /// as tracing warnings
+    pub fn process_next<C>(&mut self, context: &C) -> Result<usize>
+    where
+        C: RpdoContext,
+    {
+        let (size, _) = self.socket.recv_from(&mut self.buf)?;
+        let mut cursor = Cursor::new(&self.buf[..size]);
+        let mut writes = Vec::new();
+        while usize::try_from(cursor.position())? < size {
+            let packet = Packet::read_from(&mut cursor)?;
+            let start = usize::try_from(cursor.position())?;
+            let end = start + packet.data_len();
+            if end > size {
+                return Err(Error::InvalidData);
+            }
+            cursor.set_position(end as u64);
+            let frame = packet.frame();
+            if frame.command != Command::WriteSharedContextUnconfirmed
+                || self.source_id.is_some_and(|id| id != frame.source)
+                || self
+                    .host_id
+                    .is_some_and(|id| frame.target != 0 && frame.target != id)
+            {
+                continue;
+            }
+            let data = &self.buf[start..end];
+            let raw_data_header = RawDataHeader::read(&mut Cursor::new(data))?;
+            if raw_data_header.size != u32::try_from(data.len() - RawDataHeader::SIZE)? {
+                return Err(Error::InvalidData);
+            }
+            writes.push((
+                frame.source,
+                raw_data_header,
+                start + RawDataHeader::SIZE..end,
+            ));
+        }
+        let mut applied = 0;
+        for (source, header, range) in writes {
+            match context.set_bytes_from(source, header.register, header.offset, &self.buf[range]) {
+                Ok(()) => applied += 1,
+                Err(e) => tracing::warn!(
+                    source,
+                    register = header.register,
+                    error = %e,
+                    "published write skipped"
+                ),
+            }
+        }
+        Ok(applied)
+    }
+}
+
+/// Discover hosts with a UDP broadcast or multicast request to the target 0. Collects replies
+/// until the timeout expires
+pub fn discover(
+    bind: impl ToSocketAddrs,
+    destination: impl ToSocketAddrs,
+    timeout: Duration,
+) -> Result<Vec<(SocketAddr, DiscoveryReply)>> {
+    let socket = UdpSocket::bind(bind)?;
+    socket.set_broadcast(true)?;
+    let frame = Frame {
+        source: 0,
+        target: 0,
+        id: 0,
+        in_reply_to: 0,
+        command: Command::Discover,
+    };
+    let mut buf = Vec::with_capacity(MAX_UDP_PACKET_SIZE);
+    Packet::new(frame, 0).write_to(&mut buf)?;
+    socket.send_to(&buf, destination)?;
+    buf.resize(MAX_UDP_PACKET_SIZE, 0);
+    let deadline = Instant::now() + timeout;
+    let mut result = Vec::new();
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let (size, addr) = match socket.recv_from(&mut buf) {
+            Ok(v) => v,
+            Err(e)
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut =>
+            {
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let mut cursor = Cursor::new(&buf[..size]);
+        let Ok(packet) = Packet::read_from(&mut cursor) else {
+            continue;
+        };
+        let frame = packet.frame();
+        if frame.command != Command::Reply || frame.in_reply_to != 0 {
+            continue;
+        }
+        if let Ok(reply) = DiscoveryReply::read(&mut cursor) {
+            result.push((addr, reply));
+        }
+    }
+    Ok(result)
+}
+
+/// Streams with configurable read timeouts, required for per-call client timeouts
+pub trait ReadTimeout {
+    /// Get the stream read timeout
+    fn current_read_timeout(&self) -> std::io::Result<Option<Duration>>;
+    /// Set the stream read timeout
+    fn apply_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()>;
+}
+
+impl<S> ReadTimeout for Box<S>
+where
+    S: ReadTimeout + ?Sized,
+{
+    fn current_read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        (**self).current_read_timeout()
+    }
+    fn apply_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        (**self).apply_read_timeout(timeout)
+    }
+}
+
+impl ReadTimeout for TcpStream {
+    fn current_read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        self.read_timeout()
+    }
+    fn apply_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+}
+
+#[cfg(unix)]
+impl ReadTimeout for std::os::unix::net::UnixStream {
+    fn current_read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        self.read_timeout()
+    }
+    fn apply_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.set_read_timeout(timeout)
+    }
+}
+
+impl ReadTimeout for UdpStream {
+    fn current_read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        Ok(self.read_timeout)
+    }
+    fn apply_read_timeout(&mut self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.socket.set_read_timeout(timeout)?;
+        self.read_timeout = timeout;
+        Ok(())
+    }
+}
+
+/// Datagram streams which tell the peer of the last received packet. A single datagram stream
+/// may serve multiple peers, so the server processor created with
+/// [`SimpleServerProcessor::new_datagram`] keeps the per-peer state (e.g. the negotiated session)
+/// by the peer address
+pub trait PeerAddress {
+    /// The address of the peer the last packet has been received from
+    fn peer_address(&self) -> Option<SocketAddr>;
+}
+
+impl PeerAddress for UdpStream {
+    fn peer_address(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+}
+
+impl<S> PeerAddress for Box<S>
+where
+    S: PeerAddress + ?Sized,
+{
+    fn peer_address(&self) -> Option<SocketAddr> {
+        (**self).peer_address()
+    }
+}
+
+/// The stream read timeout getter and setter
+type ReadTimeoutFns<S> = (
+    fn(&S) -> std::io::Result<Option<Duration>>,
+    fn(&mut S, Option<Duration>) -> std::io::Result<()>,
+);
+
+/// A reader which counts the bytes read
+struct CountingReader<'a, R> {
+    inner: &'a mut R,
+    count: usize,
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.count += size;
+        Ok(size)
+    }
+}
+
+/// A helper trait for boxed read/write streams
+pub trait ReadWrite: Read + Write {}
+
+impl<T> ReadWrite for T where T: Read + Write {}
+
 /// A simple client
 pub struct SimpleClient<S>
 where
@@ -163,10 +580,37 @@ where
 {
     request_id: u32,
     stream: S,
+    source_id: u32,
     target_id: u32,
     data_buf: Vec<u8>,
     zero_copy_after: usize,
     always_flush: bool,
+    compression_threshold: usize,
+    session: Option<Session>,
+    timeout: Option<Duration>,
+    read_timeout_fns: Option<ReadTimeoutFns<S>>,
+    propagate_deadline: bool,
+    desynchronized: bool,
+}
+
+impl<S> SimpleClient<S>
+where
+    S: Read + Write + ReadTimeout,
+{
+    /// Set the per-call timeout. The stream read timeout is overridden during each call and
+    /// restored after it, replies to timed out requests are skipped
+    ///
+    /// If a call times out in the middle of a reply packet, the stream can not be used anymore and
+    /// all further calls fail, the client must be re-created with a new stream
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.set_timeout(Some(timeout));
+        self
+    }
+    /// Set the per-call timeout for the next calls, `None` to rely on the stream timeouts
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+        self.read_timeout_fns = Some((S::current_read_timeout, S::apply_read_timeout));
+    }
 }
 
 impl<S> SimpleClient<S>
@@ -178,12 +622,31 @@ where
         Self {
             request_id: 0,
             stream,
+            source_id: 0,
             target_id,
             data_buf: Vec::new(),
             zero_copy_after: DEFAULT_ZERO_COPY_AFTER,
             always_flush: true,
+            compression_threshold: compression::DEFAULT_THRESHOLD,
+            session: None,
+            timeout: None,
+            read_timeout_fns: None,
+            propagate_deadline: false,
+            desynchronized: false,
         }
     }
+    /// Send call deadlines to the target, so it drops requests which have already expired. Requires
+    /// a per-call timeout and synchronized clocks. Negotiation and heartbeats are never enveloped
+    pub fn with_deadline_propagation(mut self, propagate_deadline: bool) -> Self {
+        self.propagate_deadline = propagate_deadline;
+        self
+    }
+    /// Set the client source id (default: 0), hosts track frame sequences and heartbeats per
+    /// source and record it as the writer of registers (e.g. [`crate::context::Versioned`])
+    pub fn with_source_id(mut self, source_id: u32) -> Self {
+        self.source_id = source_id;
+        self
+    }
     /// If the data size is larger than this value, it will be sent in a separate write
     pub fn with_zero_copy_after(mut self, zero_copy_after: usize) -> Self {
         self.zero_copy_after = zero_copy_after;
@@ -194,9 +657,37 @@ where
         self.always_flush = always_flush;
         self
     }
+    /// If compression is negotiated, payloads larger than this value are compressed
+    pub fn with_compression_threshold(mut self, compression_threshold: usize) -> Self {
+        self.compression_threshold = compression_threshold;
+        self
+    }
+    /// Negotiate the protocol version and capabilities (`CAP_*` bits) with the target. The agreed
+    /// session is stored in the client. Payload compression can be negotiated only if the crate
+    /// is built with the `compression` feature
+    pub fn hello(&mut self, mut capabilities: u32) -> Result<Session> {
+        if !compression::SUPPORTED {
+            capabilities &= !CAP_COMPRESSION;
+        }
+        let mut buf = Cursor::new(Vec::new());
+        Hello::new(capabilities).write(&mut buf)?;
+        let Some(v) = self.request(Command::Hello, buf.get_ref(), true)? else {
+            return Err(Error::InvalidReply);
+        };
+        let session = Session::read(&mut Cursor::new(&v))?;
+        if session.capabilities & !capabilities != 0 {
+            return Err(Error::InvalidReply);
+        }
+        self.session = Some(session);
+        Ok(session)
+    }
+    /// The negotiated session, `None` if not negotiated
+    pub fn session(&self) -> Option<Session> {
+        self.session
+    }
     /// Ping the target
     pub fn ping(&mut self) -> Result<()> {
-        self.communicate(Command::Ping, &[], true)?;
+        self.request(Command::Ping, &[], true)?;
         Ok(())
     }
     /// Read a register
@@ -208,13 +699,132 @@ where
         };
         let mut buf = Cursor::new(Vec::new());
         raw_data_header.write(&mut buf)?;
-        let Some(v) = self.communicate(Command::ReadSharedContext, buf.get_ref(), true)? else {
+        let Some(v) = self.request(Command::ReadSharedContext, buf.get_ref(), true)? else {
             return Err(Error::InvalidReply);
         };
         Ok(v)
     }
+    /// Read a register together with the value quality
+    pub fn read_register_with_quality(
+        &mut self,
+        register: u32,
+        offset: u32,
+        size: u32,
+    ) -> Result<(Quality, Vec<u8>)> {
+        let raw_data_header = RawDataHeader {
+            register,
+            offset,
+            size,
+        };
+        let mut buf = Cursor::new(Vec::new());
+        raw_data_header.write(&mut buf)?;
+        let Some(v) = self.request(Command::ReadSharedContextQuality, buf.get_ref(), true)? else {
+            return Err(Error::InvalidReply);
+        };
+        let quality = Quality::read(&mut Cursor::new(&v))?;
+        Ok((quality, v[1..].to_vec()))
+    }
+    /// Read a register together with its metadata
+    pub fn read_register_with_metadata(
+        &mut self,
+        register: u32,
+        offset: u32,
+        size: u32,
+    ) -> Result<(RegisterMetadata, Vec<u8>)> {
+        let (metadata, data) = self.read_register_if_changed(register, offset, size, 0)?;
+        Ok((metadata, data.unwrap_or_default()))
+    }
+    /// Read a register only if its version is greater than `since_version`. The metadata is
+    /// always returned, the data is `None` if the register has not been changed
+    pub fn read_register_if_changed(
+        &mut self,
+        register: u32,
+        offset: u32,
+        size: u32,
+        since_version: u64,
+    ) -> Result<(RegisterMetadata, Option<Vec<u8>>)> {
+        let header = MetadataReadHeader {
+            register,
+            offset,
+            size,
+            since_version,
+        };
+        let mut buf = Cursor::new(Vec::new());
+        header.write(&mut buf)?;
+        let Some(v) = self.request(Command::ReadSharedContextMetadata, buf.get_ref(), true)? else {
+            return Err(Error::InvalidReply);
+        };
+        let metadata = RegisterMetadata::read(&mut Cursor::new(&v))?;
+        if since_version > 0 && metadata.version <= since_version {
+            return Ok((metadata, None));
+        }
+        Ok((metadata, Some(v[RegisterMetadata::SIZE..].to_vec())))
+    }
+    /// Read register history samples within the time range (nanoseconds since the UNIX epoch,
+    /// inclusive), oldest first. If `max_samples` is not zero, at most `max_samples` are returned
+    pub fn read_history(
+        &mut self,
+        register: u32,
+        from: u64,
+        to: u64,
+        max_samples: u32,
+    ) -> Result<Vec<HistorySample>> {
+        let header = HistoryReadHeader {
+            register,
+            from,
+            to,
+            max_samples,
+        };
+        let mut buf = Cursor::new(Vec::with_capacity(HistoryReadHeader::SIZE));
+        header.write(&mut buf)?;
+        let Some(v) = self.request(Command::ReadHistory, buf.get_ref(), true)? else {
+            return Err(Error::InvalidReply);
+        };
+        let reply = HistoryReply::read(&mut Cursor::new(&v))?;
+        Ok(reply.samples)
+    }
     /// Write a register
     pub fn write_register(&mut self, register: u32, offset: u32, data: &[u8]) -> Result<()> {
+        self.write_register_from(self.source_id, register, offset, data)
+    }
+    /// Write a register on behalf of a source (e.g. by a gateway), the target records the source
+    /// as the writer
+    pub fn write_register_from(
+        &mut self,
+        source: u32,
+        register: u32,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<()> {
+        let raw_data_header = RawDataHeader {
+            register,
+            offset,
+            size: u32::try_from(data.len())?,
+        };
+        let mut buf = Cursor::new(Vec::new());
+        raw_data_header.write(&mut buf)?;
+        buf.write_all(data)?;
+        let reply = self.send_raw(
+            source,
+            self.target_id,
+            Command::WriteSharedContext,
+            buf.get_ref(),
+            true,
+        )?;
+        if let Some((frame, data)) = reply {
+            if frame.command == Command::Error {
+                return Err(Error::from(data.as_slice()));
+            }
+        }
+        Ok(())
+    }
+    /// Write a register with no reply
+    pub fn write_register_unconfirmed(
+        &mut self,
+        register: u32,
+        offset: u32,
+        data: &[u8],
+    ) -> Result<()> {
         let raw_data_header = RawDataHeader {
             register,
             offset,
@@ -223,56 +833,248 @@ where
         let mut buf = Cursor::new(Vec::new());
         raw_data_header.write(&mut buf)?;
         buf.write_all(data)?;
-        self.communicate(Command::WriteSharedContext, buf.get_ref(), true)?;
+        self.request(Command::WriteSharedContextUnconfirmed, buf.get_ref(), false)?;
         Ok(())
     }
-    /// Communicate with the target
+    /// Send a heartbeat, the target runs its safe-state actions if no next heartbeat is received
+    /// within the interval. Zero interval disarms the target watchdog. The target watches clients
+    /// by their source ids, so the client must have a unique non-zero one
+    pub fn heartbeat(&mut self, interval: Duration) -> Result<()> {
+        let mut buf = Cursor::new(Vec::new());
+        Heartbeat::new(interval).write(&mut buf)?;
+        self.request(Command::Heartbeat, buf.get_ref(), true)?;
+        Ok(())
+    }
+    /// List custom commands registered on the target
+    pub fn list_commands(&mut self) -> Result<Vec<CommandInfo>> {
+        let Some(v) = self.request(Command::ListCommands, &[], true)? else {
+            return Err(Error::InvalidReply);
+        };
+        Ok(CommandList::read(&mut Cursor::new(&v))?.commands)
+    }
+    /// Call a custom command with a typed request and reply (packed with binrw, little-endian)
+    pub fn call<Req, Resp>(&mut self, code: u16, request: &Req) -> Result<Resp>
+    where
+        Req: for<'a> BinWrite<Args<'a> = ()>,
+        Resp: for<'a> BinRead<Args<'a> = ()>,
+    {
+        let mut buf = Cursor::new(Vec::new());
+        request.write_le(&mut buf)?;
+        let Some(v) = self.request(Command::Other(code), buf.get_ref(), true)? else {
+            return Err(Error::InvalidReply);
+        };
+        Resp::read_le(&mut Cursor::new(&v)).map_err(Into::into)
+    }
+    /// Get the target redundancy status
+    pub fn redundancy_status(&mut self) -> Result<RedundancyStatus> {
+        let Some(v) = self.request(Command::RedundancyStatus, &[], true)? else {
+            return Err(Error::InvalidReply);
+        };
+        RedundancyStatus::read(&mut Cursor::new(&v)).map_err(Into::into)
+    }
+    /// Communicate with the target, the reply data is returned as-is (error replies are not
+    /// converted into errors)
     pub fn communicate(
         &mut self,
         command: Command,
         data: &[u8],
         wait_reply: bool,
     ) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .communicate_raw(self.target_id, command, data, wait_reply)?
+            .map(|(_, data)| data))
+    }
+    /// Send a request to the target, error replies are converted into errors
+    pub fn request(
+        &mut self,
+        command: Command,
+        data: &[u8],
+        wait_reply: bool,
+    ) -> Result<Option<Vec<u8>>> {
+        let Some((frame, data)) =
+            self.communicate_raw(self.target_id, command, data, wait_reply)?
+        else {
+            return Ok(None);
+        };
+        if frame.command == Command::Error {
+            return Err(Error::from(data.as_slice()));
+        }
+        Ok(Some(data))
+    }
+    /// Communicate with a specific target, the reply frame and data are returned as-is (error
+    /// replies are not converted into errors)
+    pub fn communicate_raw(
+        &mut self,
+        target: u32,
+        command: Command,
+        data: &[u8],
+        wait_reply: bool,
+    ) -> Result<Option<(Frame, Vec<u8>)>> {
+        self.send_raw(self.source_id, target, command, data, wait_reply)
+    }
+    /// Forward a frame on behalf of its source (e.g. by a router), the source, the target and
+    /// the command of the frame are kept, the id is replaced with the client request id. The reply
+    /// frame and data are returned as-is
+    pub fn forward_raw(
+        &mut self,
+        frame: &Frame,
+        data: &[u8],
+        wait_reply: bool,
+    ) -> Result<Option<(Frame, Vec<u8>)>> {
+        self.send_raw(frame.source, frame.target, frame.command, data, wait_reply)
+    }
+    fn send_raw(
+        &mut self,
+        source: u32,
+        target: u32,
+        command: Command,
+        data: &[u8],
+        wait_reply: bool,
+    ) -> Result<Option<(Frame, Vec<u8>)>> {
+        if self.desynchronized {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "the stream is desynchronized after a timed out read",
+            )));
+        }
         let request_id = self.request_id;
-        self.request_id += 1;
+        self.request_id = self.request_id.wrapping_add(1);
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let enveloped;
+        let (command, data) = match self.timeout {
+            // the peer state must be changed even if the request is late
+            Some(timeout)
+                if self.propagate_deadline
+                    && !matches!(command, Command::Hello | Command::Heartbeat) =>
+            {
+                let mut buf = Cursor::new(Vec::with_capacity(DeadlineHeader::SIZE + data.len()));
+                DeadlineHeader::new(SystemTime::now() + timeout, command).write(&mut buf)?;
+                buf.get_mut().extend(data);
+                enveloped = buf.into_inner();
+                (Command::Deadline, enveloped.as_slice())
+            }
+            _ => (command, data),
+        };
+        // the target version is not known until the negotiation is complete
+        let version = if command == Command::Hello {
+            MIN_VERSION
+        } else {
+            self.session.map_or(VERSION, |session| session.version)
+        };
         let frame = Frame {
-            source: 0,
-            target: self.target_id,
+            source,
+            target,
             id: request_id,
             in_reply_to: 0,
             command,
         };
-        let packet = Packet::new(frame, data.len());
-        if data.len() > self.zero_copy_after {
-            packet.write_to(&mut self.stream)?;
-            self.stream.write_all(data)?;
-            self.stream.flush()?;
+        let encoded = compression::is_negotiated(self.session);
+        let (encoding, data) = if encoded {
+            let (encoding, data) = compression::encode(data, self.compression_threshold);
+            (Some(encoding), data)
         } else {
-            self.data_buf.reserve(packet.size_full());
-            self.data_buf.clear();
-            packet.write_to(&mut Cursor::new(&mut self.data_buf))?;
-            self.data_buf.extend(data);
-            self.stream.write_all(&self.data_buf)?;
-            if self.always_flush {
-                self.stream.flush()?;
-            }
-        }
+            (None, Cow::Borrowed(data))
+        };
+        write_packet(
+            &mut self.stream,
+            &mut self.data_buf,
+            Packet::new(frame, 0).with_version(version),
+            encoding,
+            &data,
+            self.zero_copy_after,
+            self.always_flush,
+        )?;
         if !wait_reply {
             return Ok(None);
         }
-        let packet = Packet::read_from(&mut self.stream)?;
-        let data_len = packet.data_len();
-        self.data_buf.resize(data_len, 0);
-        self.stream.read_exact(&mut self.data_buf)?;
+        let original_timeout = match (deadline, self.read_timeout_fns) {
+            (Some(_), Some((get_read_timeout, _))) => Some(get_read_timeout(&self.stream)?),
+            _ => None,
+        };
+        let result = self.read_reply(source, request_id, deadline);
+        if let (Some(timeout), Some((_, set_read_timeout))) =
+            (original_timeout, self.read_timeout_fns)
+        {
+            set_read_timeout(&mut self.stream, timeout)?;
+        }
+        let packet = result?;
         let frame = packet.frame();
-        if frame.target != 0 || frame.in_reply_to != request_id {
+        let data = if encoded {
+            compression::decode(&self.data_buf)?.into_owned()
+        } else {
+            self.data_buf.clone()
+        };
+        Ok(Some((frame.clone(), data)))
+    }
+    fn read_reply(
+        &mut self,
+        source: u32,
+        request_id: u32,
+        deadline: Option<Instant>,
+    ) -> Result<Packet> {
+        loop {
+            let packet = self.read_reply_packet(deadline)?;
+            let frame = packet.frame();
+            if frame.target == source {
+                if frame.in_reply_to == request_id {
+                    return Ok(packet);
+                }
+                // a late reply to a previous (timed out) request
+                let behind = request_id.wrapping_sub(frame.in_reply_to);
+                if behind != 0 && behind < 1 << 31 {
+                    tracing::debug!(in_reply_to = frame.in_reply_to, "stale reply skipped");
+                    continue;
+                }
+            }
             return Err(Error::InvalidReply);
         }
-        Ok(Some(self.data_buf.clone()))
+    }
+    fn read_reply_packet(&mut self, deadline: Option<Instant>) -> Result<Packet> {
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(Error::Timeout);
+            }
+            if let Some((_, set_read_timeout)) = self.read_timeout_fns {
+                set_read_timeout(&mut self.stream, Some(remaining))?;
+            }
+        }
+        let mut reader = CountingReader {
+            inner: &mut self.stream,
+            count: 0,
+        };
+        let result = Packet::read_from(&mut reader).and_then(|packet| {
+            self.data_buf.resize(packet.data_len(), 0);
+            reader.read_exact(&mut self.data_buf)?;
+            Ok(packet)
+        });
+        match result {
+            Err(Error::Io(e))
+                if deadline.is_some()
+                    && matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+            {
+                if reader.count == 0 {
+                    return Err(Error::Timeout);
+                }
+                // the rest of the packet may arrive later and be taken as a new one
+                self.desynchronized = true;
+                Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "timed out in the middle of a packet, the stream is desynchronized",
+                )))
+            }
+            v => v,
+        }
     }
 }
 
 /// A simple server processor
+///
+/// Processors created with [`SimpleServerProcessor::new_datagram`] keep the negotiated sessions
+/// per peer, so a single processor may serve multiple peers of a datagram stream
 pub struct SimpleServerProcessor<CTX, HOST, S>
 where
     CTX: RpdoContext,
@@ -284,6 +1086,28 @@ where
     data_buf: Vec<u8>,
     zero_copy_after: usize,
     always_flush: bool,
+    compression_threshold: usize,
+    sessions: BTreeMap<Option<SocketAddr>, Session>,
+    peer_address: fn(&S) -> Option<SocketAddr>,
+    connection_id: u64,
+    trace: Trace,
+    span: tracing::Span,
+}
+
+impl<CTX, HOST, S> SimpleServerProcessor<CTX, HOST, S>
+where
+    CTX: RpdoContext,
+    HOST: SyncHost<Context = CTX>,
+    S: Read + Write + PeerAddress,
+{
+    /// Create a new server processor for a datagram stream which serves multiple peers, the
+    /// negotiated sessions are kept by the peer addresses
+    pub fn new_datagram(host: HOST, stream: S) -> Self {
+        Self {
+            peer_address: S::peer_address,
+            ..Self::new(host, stream)
+        }
+    }
 }
 
 impl<CTX, HOST, S> SimpleServerProcessor<CTX, HOST, S>
@@ -292,20 +1116,42 @@ where
     HOST: SyncHost<Context = CTX>,
     S: Read + Write,
 {
-    /// Create a new server processor
+    /// Create a new server processor for a single-peer stream (e.g. a TCP connection or a serial
+    /// port)
     pub fn new(host: HOST, stream: S) -> Self
     where
         HOST: SyncHost,
     {
+        let connection_id = NEXT_CONNECTION_ID.fetch_add(1, atomic::Ordering::Relaxed);
         Self {
             host,
             stream,
             data_buf: Vec::new(),
             zero_copy_after: DEFAULT_ZERO_COPY_AFTER,
             always_flush: true,
+            compression_threshold: compression::DEFAULT_THRESHOLD,
+            sessions: BTreeMap::new(),
+            peer_address: |_| None,
+            connection_id,
+            trace: Trace::default(),
+            span: Trace::default().connection_span(connection_id),
         }
     }
 
+    /// Set the level of the connection tracing span and events (default: DEBUG). Processing
+    /// errors are always reported as warnings
+    pub fn with_trace_level(mut self, level: tracing::Level) -> Self {
+        self.trace = Trace::new(level);
+        self.span = self.trace.connection_span(self.connection_id);
+        self
+    }
+
+    /// Set a custom connection tracing span (e.g. with the peer address)
+    pub fn with_span(mut self, span: tracing::Span) -> Self {
+        self.span = span;
+        self
+    }
+
     /// If the data size is larger than this value, it will be sent in a separate write
     pub fn with_zero_copy_after(mut self, zero_copy_after: usize) -> Self {
         self.zero_copy_after = zero_copy_after;
@@ -318,29 +1164,137 @@ where
         self
     }
 
-    /// Process the next packet
+    /// If compression is negotiated, payloads larger than this value are compressed
+    pub fn with_compression_threshold(mut self, compression_threshold: usize) -> Self {
+        self.compression_threshold = compression_threshold;
+        self
+    }
+
+    /// The session negotiated by the peer of the last received packet, `None` if not negotiated
+    pub fn session(&self) -> Option<Session> {
+        self.sessions
+            .get(&(self.peer_address)(&self.stream))
+            .copied()
+    }
+
+    /// Process the next packet. Errors are also reported as tracing events
     pub fn process_next(&mut self) -> Result<()> {
+        let span = self.span.clone();
+        let _entered = span.enter();
+        let result = self.process_packet();
+        if let Err(ref e) = result {
+            match e {
+                Error::Io(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    dyn_event!(self.trace.level(), "connection closed");
+                }
+                Error::Io(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    dyn_event!(self.trace.level(), error = %e, "connection idle");
+                }
+                e => tracing::warn!(error = %e, "packet processing failed"),
+            }
+        }
+        result
+    }
+
+    fn process_packet(&mut self) -> Result<()> {
         let packet = Packet::read_from(&mut self.stream)?;
         self.data_buf.resize(packet.data_len(), 0);
         self.stream.read_exact(&mut self.data_buf)?;
         let frame = packet.frame();
-        if let Some((reply, data)) = self.host.process_frame(frame, &self.data_buf)? {
-            let packet = Packet::new(reply, data.len());
-            if data.len() > self.zero_copy_after {
-                packet.write_to(&mut self.stream)?;
-                self.stream.write_all(&data)?;
-                self.stream.flush()?;
-            } else {
-                self.data_buf.reserve(packet.size_full());
-                self.data_buf.clear();
-                packet.write_to(&mut Cursor::new(&mut self.data_buf))?;
-                self.data_buf.extend(data);
-                self.stream.write_all(&self.data_buf)?;
-                if self.always_flush {
-                    self.stream.flush()?;
+        let peer = (self.peer_address)(&self.stream);
+        // the encoding switches only after the hello exchange is complete
+        let encoded = compression::is_negotiated(self.sessions.get(&peer).copied());
+        let request = if encoded {
+            compression::decode(&self.data_buf)?
+        } else {
+            Cow::Borrowed(self.data_buf.as_slice())
+        };
+        if let Some((reply, mut data)) = self.host.process_frame(frame, &request)? {
+            if reply.command == Command::Reply
+                && frame.enclosed_command(&request)? == Command::Hello
+            {
+                let session = Session::read(&mut Cursor::new(&data))?;
+                if let Some(refused) = self.store_session(peer, session) {
+                    data.clear();
+                    refused.write(&mut Cursor::new(&mut data))?;
                 }
             }
+            // error details are sent only to peers which have negotiated the version 1
+            if reply.command == Command::Error && !self.sessions.contains_key(&peer) {
+                error::strip_details(&mut data);
+            }
+            let (encoding, data) = if encoded {
+                let (encoding, data) = compression::encode(&data, self.compression_threshold);
+                (Some(encoding), data)
+            } else {
+                (None, Cow::Borrowed(data.as_slice()))
+            };
+            // replies are framed with the version of the request
+            write_packet(
+                &mut self.stream,
+                &mut self.data_buf,
+                Packet::new(reply, 0).with_version(packet.version()),
+                encoding,
+                &data,
+                self.zero_copy_after,
+                self.always_flush,
+            )?;
         }
         Ok(())
     }
+
+    /// Store the peer session. If there are too many peers, compression is refused for new ones,
+    /// the session to send instead is returned
+    fn store_session(&mut self, peer: Option<SocketAddr>, session: Session) -> Option<Session> {
+        if !self.sessions.contains_key(&peer) && self.sessions.len() >= MAX_PEER_SESSIONS {
+            return compression::is_negotiated(Some(session)).then_some(Session {
+                capabilities: session.capabilities & !CAP_COMPRESSION,
+                ..session
+            });
+        }
+        self.sessions.insert(peer, session);
+        None
+    }
+}
+
+/// Write a packet to the stream, the data length of the packet is set from the data, which is
+/// prefixed with the payload encoding flag if set. If the data size is larger than
+/// `zero_copy_after`, it is written directly from the source
+fn write_packet<S: Write>(
+    stream: &mut S,
+    buf: &mut Vec<u8>,
+    packet: Packet,
+    encoding: Option<u8>,
+    data: &[u8],
+    zero_copy_after: usize,
+    always_flush: bool,
+) -> Result<()> {
+    let prefix = encoding
+        .as_ref()
+        .map(std::slice::from_ref)
+        .unwrap_or_default();
+    let packet = Packet::new(packet.frame().clone(), prefix.len() + data.len())
+        .with_version(packet.version());
+    if data.len() > zero_copy_after {
+        packet.write_to(stream)?;
+        stream.write_all(prefix)?;
+        stream.write_all(data)?;
+        stream.flush()?;
+    } else {
+        buf.reserve(packet.size_full());
+        buf.clear();
+        packet.write_to(&mut Cursor::new(&mut *buf))?;
+        buf.extend(prefix);
+        buf.extend(data);
+        stream.write_all(buf)?;
+        if always_flush {
+            stream.flush()?;
+        }
+    }
+    Ok(())
 }
@@ -5,15 +5,26 @@
 // TODO nostd
 /// Communication
 pub mod comm;
+mod compression;
 /// Shared context
 pub mod context;
+/// Authenticated encryption
+#[cfg(feature = "encryption")]
+pub mod crypto;
 mod error;
 /// Host
 pub mod host;
 /// I/O helpers
 pub mod io;
+/// Hot-standby redundancy
+pub mod redundancy;
+/// Frame routing between hosts
+pub mod router;
+mod trace;
+/// Connection watchdog
+pub mod watchdog;
 
-pub use error::Error;
+pub use error::{Error, ErrorDetails, UserErrorCode, ERR_USER_MIN};
 
 /// Result type
 pub type Result<T> = std::result::Result<T, error::Error>;
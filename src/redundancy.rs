@@ -0,0 +1,217 @@
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use binrw::prelude::*;
+
+use crate::context::RpdoContext;
+use crate::io::SimpleClient;
+use crate::Result;
+
+const DEFAULT_MAX_FAILURES: u32 = 3;
+
+/// Host redundancy role
+#[binrw]
+#[brw(little, repr = u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Role {
+    /// The host serves clients
+    Primary = 0,
+    /// The host replicates the primary and rejects client writes
+    Standby = 1,
+}
+
+/// Redundancy status structure
+#[binrw]
+#[brw(little)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RedundancyStatus {
+    /// The host role
+    pub role: Role,
+    /// The redundancy epoch, incremented on each takeover
+    pub epoch: u64,
+}
+
+struct RedundancyInner {
+    role: AtomicU8,
+    epoch: AtomicU64,
+}
+
+/// Shared redundancy state of a host
+///
+/// A host in [`Role::Standby`] rejects client writes with [`crate::Error::Standby`] (unconfirmed writes
+/// are dropped). The epoch is incremented by a standby which takes over, a primary which sees its
+/// peer as a primary with a greater epoch demotes itself, so a recovered former primary does not
+/// keep accepting writes.
+#[derive(Clone)]
+pub struct Redundancy {
+    inner: Arc<RedundancyInner>,
+}
+
+impl Redundancy {
+    /// Create a new redundancy state. A primary starts with the epoch 1, a standby with 0
+    pub fn new(role: Role) -> Self {
+        Self {
+            inner: Arc::new(RedundancyInner {
+                role: AtomicU8::new(role as u8),
+                epoch: AtomicU64::new(u64::from(role == Role::Primary)),
+            }),
+        }
+    }
+    /// The current role
+    pub fn role(&self) -> Role {
+        if self.inner.role.load(Ordering::Acquire) == Role::Primary as u8 {
+            Role::Primary
+        } else {
+            Role::Standby
+        }
+    }
+    /// The current epoch
+    pub fn epoch(&self) -> u64 {
+        self.inner.epoch.load(Ordering::Acquire)
+    }
+    /// Is the host primary
+    pub fn is_primary(&self) -> bool {
+        self.role() == Role::Primary
+    }
+    /// The current status
+    pub fn status(&self) -> RedundancyStatus {
+        RedundancyStatus {
+            role: self.role(),
+            epoch: self.epoch(),
+        }
+    }
+    /// Become primary with a new epoch (greater than the current and the given ones)
+    pub fn promote(&self, seen_epoch: u64) -> u64 {
+        let epoch = self.epoch().max(seen_epoch) + 1;
+        self.inner.epoch.store(epoch, Ordering::Release);
+        self.inner
+            .role
+            .store(Role::Primary as u8, Ordering::Release);
+        tracing::warn!(epoch, "redundancy: promoted to primary");
+        epoch
+    }
+    /// Become standby, adopting the given epoch if it is greater than the current one
+    pub fn demote(&self, seen_epoch: u64) {
+        self.inner.epoch.fetch_max(seen_epoch, Ordering::AcqRel);
+        if self.inner.role.swap(Role::Standby as u8, Ordering::AcqRel) == Role::Primary as u8 {
+            tracing::warn!(epoch = self.epoch(), "redundancy: demoted to standby");
+        }
+    }
+    fn adopt_epoch(&self, seen_epoch: u64) {
+        self.inner.epoch.fetch_max(seen_epoch, Ordering::AcqRel);
+    }
+}
+
+/// Peer connection function
+pub type ConnectFn<S> = Box<dyn FnMut() -> Result<SimpleClient<S>> + Send>;
+
+/// Redundancy peer monitor
+///
+/// Monitors the peer host with status requests. A standby replicates the configured registers
+/// of the primary into the local context and takes over when the primary does not respond for
+/// the configured number of checks in a row. A primary demotes itself if the peer has taken
+/// over with a greater epoch.
+pub struct Monitor<CTX, S>
+where
+    CTX: RpdoContext,
+    S: Read + Write,
+{
+    redundancy: Redundancy,
+    context: CTX,
+    connect: ConnectFn<S>,
+    client: Option<SimpleClient<S>>,
+    registers: Vec<Range<u32>>,
+    failures: u32,
+    max_failures: u32,
+}
+
+impl<CTX, S> Monitor<CTX, S>
+where
+    CTX: RpdoContext,
+    S: Read + Write,
+{
+    /// Create a new monitor. The connect function is called to (re)connect to the peer
+    pub fn new<F>(redundancy: Redundancy, context: CTX, connect: F) -> Self
+    where
+        F: FnMut() -> Result<SimpleClient<S>> + Send + 'static,
+    {
+        Self {
+            redundancy,
+            context,
+            connect: Box::new(connect),
+            client: None,
+            registers: Vec::new(),
+            failures: 0,
+            max_failures: DEFAULT_MAX_FAILURES,
+        }
+    }
+    /// Replicate the register range (whole registers are copied)
+    pub fn with_registers(mut self, registers: Range<u32>) -> Self {
+        self.registers.push(registers);
+        self
+    }
+    /// Take over after the given number of failed checks in a row (default: 3)
+    pub fn with_max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = max_failures;
+        self
+    }
+    /// Perform a single check (and replication if standby). Only failed connections and status
+    /// requests are counted as failed checks, replication errors are returned as-is
+    pub fn step(&mut self) -> Result<()> {
+        let peer = match self.peer_status() {
+            Ok(v) => {
+                self.failures = 0;
+                v
+            }
+            Err(e) => {
+                self.client.take();
+                self.failures += 1;
+                if !self.redundancy.is_primary() && self.failures >= self.max_failures {
+                    self.redundancy.promote(0);
+                }
+                return Err(e);
+            }
+        };
+        self.follow(peer)
+    }
+    /// Run the monitor loop
+    pub fn run(&mut self, interval: Duration) {
+        for _ in rtsc::time::interval(interval) {
+            if let Err(e) = self.step() {
+                tracing::debug!(error = %e, failures = self.failures, "redundancy: peer check failed");
+            }
+        }
+    }
+    fn peer_status(&mut self) -> Result<RedundancyStatus> {
+        if self.client.is_none() {
+            self.client = Some((self.connect)()?);
+        }
+        self.client.as_mut().unwrap().redundancy_status()
+    }
+    fn follow(&mut self, peer: RedundancyStatus) -> Result<()> {
+        if self.redundancy.is_primary() {
+            if peer.role == Role::Primary && peer.epoch > self.redundancy.epoch() {
+                self.redundancy.demote(peer.epoch);
+            }
+            return Ok(());
+        }
+        if peer.role != Role::Primary {
+            return Ok(());
+        }
+        self.redundancy.adopt_epoch(peer.epoch);
+        let Some(client) = self.client.as_mut() else {
+            return Ok(());
+        };
+        for range in &self.registers {
+            for register in range.clone() {
+                let data = client.read_register(register, 0, 0)?;
+                self.context.set_bytes(register, 0, &data)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Cursor, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use binrw::BinRead;
+
+use crate::comm::{Command, DeadlineHeader, Frame};
+use crate::error::Error;
+use crate::host::SyncHost;
+use crate::io::{ReadTimeout, SimpleClient};
+use crate::{Mutex, Result};
+
+trait Transport: Read + Write + ReadTimeout + Send {}
+
+impl<T> Transport for T where T: Read + Write + ReadTimeout + Send {}
+
+type BoxedClient = SimpleClient<Box<dyn Transport>>;
+type Connector = Box<dyn FnMut() -> Result<BoxedClient> + Send>;
+
+struct DownstreamInner {
+    client: Option<BoxedClient>,
+    connector: Option<Connector>,
+    timeout: Option<Duration>,
+}
+
+/// A downstream connection (TCP, UDP, serial etc.) which can be shared by multiple routes, e.g.
+/// by all hosts of a RS-485 segment
+///
+/// Requests to the same downstream are serialized, set a timeout with
+/// [`Downstream::with_timeout`] so a hung downstream host does not block the others.
+#[derive(Clone)]
+pub struct Downstream {
+    inner: Arc<Mutex<DownstreamInner>>,
+}
+
+impl Downstream {
+    /// Create a new downstream over an established stream
+    pub fn new<S>(stream: S) -> Self
+    where
+        S: Read + Write + ReadTimeout + Send + 'static,
+    {
+        Self {
+            inner: Arc::new(Mutex::new(DownstreamInner {
+                client: Some(SimpleClient::new(Box::new(stream), 0)),
+                connector: None,
+                timeout: None,
+            })),
+        }
+    }
+    /// Create a new downstream with a connect function. The function is called on the first
+    /// request and after I/O errors to re-establish the stream
+    pub fn with_connector<S, F>(mut connect: F) -> Self
+    where
+        S: Read + Write + ReadTimeout + Send + 'static,
+        F: FnMut() -> Result<S> + Send + 'static,
+    {
+        Self {
+            inner: Arc::new(Mutex::new(DownstreamInner {
+                client: None,
+                connector: Some(Box::new(move || {
+                    Ok(SimpleClient::new(
+                        Box::new(connect()?) as Box<dyn Transport>,
+                        0,
+                    ))
+                })),
+                timeout: None,
+            })),
+        }
+    }
+    /// Set the reply timeout of forwarded requests, requests which are not replied in time are
+    /// replied with [`Error::Timeout`]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        {
+            let mut inner = self.inner.lock();
+            inner.timeout = Some(timeout);
+            if let Some(ref mut client) = inner.client {
+                client.set_timeout(Some(timeout));
+            }
+        }
+        self
+    }
+    fn forward(
+        &self,
+        frame: &Frame,
+        data: &[u8],
+        wait_reply: bool,
+    ) -> Result<Option<(Frame, Vec<u8>)>> {
+        let mut inner = self.inner.lock();
+        if inner.client.is_none() {
+            let Some(ref mut connector) = inner.connector else {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "downstream disconnected",
+                )));
+            };
+            let mut client = connector()?;
+            if let Some(timeout) = inner.timeout {
+                client.set_timeout(Some(timeout));
+            }
+            inner.client = Some(client);
+        }
+        let result = inner
+            .client
+            .as_mut()
+            .unwrap()
+            .forward_raw(frame, data, wait_reply);
+        if result
+            .as_ref()
+            .is_err_and(|e| matches!(e.kind(), Error::Io(_)))
+            && inner.connector.is_some()
+        {
+            inner.client.take();
+        }
+        result
+    }
+}
+
+/// A router which forwards frames to downstream hosts by the frame target id
+///
+/// Frames for the targets which have no route are processed by the local host (which replies
+/// with [`Error::UnknownHost`] if the target does not match). Frames are forwarded with their
+/// original source, so downstream hosts track writers, sequences and heartbeats per client.
+/// Replies of downstream hosts are sent back with rewritten ids, forwarding errors are replied as
+/// error frames.
+///
+/// Custom commands are forwarded waiting for a reply, unless registered with
+/// [`Router::with_unconfirmed_command`].
+#[derive(Clone)]
+pub struct Router<H>
+where
+    H: SyncHost,
+{
+    host: H,
+    routes: Arc<BTreeMap<u32, Downstream>>,
+    unconfirmed_commands: Arc<BTreeSet<u16>>,
+}
+
+impl<H> Router<H>
+where
+    H: SyncHost,
+{
+    /// Create a new router with the local host
+    pub fn new(host: H) -> Self {
+        Self {
+            host,
+            routes: <_>::default(),
+            unconfirmed_commands: <_>::default(),
+        }
+    }
+    /// Add a route to a downstream host
+    pub fn with_route(mut self, target: u32, downstream: Downstream) -> Self {
+        Arc::make_mut(&mut self.routes).insert(target, downstream);
+        self
+    }
+    /// Mark a custom command as having no reply
+    pub fn with_unconfirmed_command(mut self, command: Command) -> Self {
+        Arc::make_mut(&mut self.unconfirmed_commands).insert(command.code());
+        self
+    }
+    /// Check if there is a route to the target
+    pub fn has_route(&self, target: u32) -> bool {
+        self.routes.contains_key(&target)
+    }
+    fn forward(
+        &self,
+        downstream: &Downstream,
+        frame: &Frame,
+        data: &[u8],
+    ) -> Result<Option<(Frame, Vec<u8>)>> {
+        let command = if frame.command == Command::Deadline {
+            let header = DeadlineHeader::read(&mut Cursor::new(data))?;
+            if header.is_expired() {
+                return Ok(None);
+            }
+            header.command
+        } else {
+            frame.command
+        };
+        let wait_reply = command != Command::WriteSharedContextUnconfirmed
+            && !self.unconfirmed_commands.contains(&command.code());
+        match downstream.forward(frame, data, wait_reply) {
+            Ok(Some((reply, reply_data))) => {
+                let mut reply_frame = self
+                    .host
+                    .create_frame(frame.source, frame.id, reply.command);
+                reply_frame.source = frame.target;
+                Ok(Some((reply_frame, reply_data)))
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                tracing::warn!(target = frame.target, error = %e, "router: forwarding failed");
+                if !wait_reply {
+                    return Ok(None);
+                }
+                let mut reply_frame =
+                    self.host
+                        .create_frame(frame.source, frame.id, Command::Error);
+                reply_frame.source = frame.target;
+                Ok(Some((reply_frame, e.into())))
+            }
+        }
+    }
+}
+
+impl<H> SyncHost for Router<H>
+where
+    H: SyncHost,
+{
+    type Context = H::Context;
+
+    fn host_id_matches(&self, frame: &Frame) -> bool {
+        self.has_route(frame.target) || self.host.host_id_matches(frame)
+    }
+
+    fn create_frame(&self, target: u32, in_reply_to: u32, command: Command) -> Frame {
+        self.host.create_frame(target, in_reply_to, command)
+    }
+
+    fn process_frame(&self, frame: &Frame, data: &[u8]) -> Result<Option<(Frame, Vec<u8>)>> {
+        // replies and the connection negotiation are never forwarded
+        if !matches!(
+            frame.enclosed_command(data)?,
+            Command::Reply | Command::Error | Command::Hello
+        ) {
+            if let Some(downstream) = self.routes.get(&frame.target) {
+                return self.forward(downstream, frame, data);
+            }
+        }
+        self.host.process_frame(frame, data)
+    }
+}
@@ -0,0 +1,66 @@
+use crate::comm::Frame;
+
+/// Create a span with a level chosen at run time
+macro_rules! dyn_span {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            tracing::Level::ERROR => tracing::error_span!($($arg)+),
+            tracing::Level::WARN => tracing::warn_span!($($arg)+),
+            tracing::Level::INFO => tracing::info_span!($($arg)+),
+            tracing::Level::DEBUG => tracing::debug_span!($($arg)+),
+            _ => tracing::trace_span!($($arg)+),
+        }
+    };
+}
+
+/// Emit an event with a level chosen at run time
+macro_rules! dyn_event {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            tracing::Level::ERROR => tracing::error!($($arg)+),
+            tracing::Level::WARN => tracing::warn!($($arg)+),
+            tracing::Level::INFO => tracing::info!($($arg)+),
+            tracing::Level::DEBUG => tracing::debug!($($arg)+),
+            _ => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+pub(crate) use {dyn_event, dyn_span};
+
+/// The level of per-connection and per-frame spans and events (default: DEBUG)
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Trace {
+    level: tracing::Level,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new(tracing::Level::DEBUG)
+    }
+}
+
+impl Trace {
+    pub(crate) fn new(level: tracing::Level) -> Self {
+        Self { level }
+    }
+    pub(crate) fn level(self) -> tracing::Level {
+        self.level
+    }
+    /// A span for a processed connection
+    pub(crate) fn connection_span(self, id: u64) -> tracing::Span {
+        dyn_span!(self.level, "connection", id)
+    }
+    /// A span for a frame processed by a host
+    pub(crate) fn frame_span(self, host: u32, frame: &Frame) -> tracing::Span {
+        dyn_span!(
+            self.level,
+            "frame",
+            host,
+            source = frame.source,
+            target = frame.target,
+            id = frame.id,
+            command = ?frame.command
+        )
+    }
+}
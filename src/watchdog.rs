@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::context::RpdoContext;
+use crate::error::Error;
+use crate::{Mutex, Result};
+
+type Action = dyn Fn(u32) -> Result<()> + Send + Sync;
+type EventHandler = dyn Fn(WatchdogEvent) + Send + Sync;
+
+/// Watchdog event
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WatchdogEvent {
+    /// A client has declared its heartbeat interval
+    Connected {
+        /// The client source id
+        source: u32,
+        /// The declared heartbeat interval
+        interval: Duration,
+    },
+    /// A client heartbeat has lapsed, the safe-state actions have been run
+    Expired {
+        /// The client source id
+        source: u32,
+    },
+    /// A client has recovered after its heartbeat had lapsed
+    Recovered {
+        /// The client source id
+        source: u32,
+    },
+    /// A client has disarmed its watchdog
+    Disarmed {
+        /// The client source id
+        source: u32,
+    },
+}
+
+struct Client {
+    interval: Duration,
+    last_heartbeat: Instant,
+    expired: bool,
+}
+
+/// A connection watchdog. Clients declare their heartbeat intervals with
+/// [`crate::comm::Command::Heartbeat`], if a heartbeat lapses, the configured safe-state actions
+/// are run
+///
+/// Clients are watched by their source ids, so each client must set a unique one (see
+/// [`crate::io::SimpleClient::with_source_id`]), heartbeats of the anonymous source 0 are rejected
+#[derive(Clone, Default)]
+pub struct Watchdog {
+    clients: Arc<Mutex<BTreeMap<u32, Client>>>,
+    actions: Vec<Arc<Action>>,
+    event_handler: Option<Arc<EventHandler>>,
+}
+
+impl Watchdog {
+    /// Create a new watchdog
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Write the data to a register when a heartbeat lapses
+    pub fn with_safe_value<CTX>(self, context: CTX, register: u32, offset: u32, data: &[u8]) -> Self
+    where
+        CTX: RpdoContext + Send + Sync + 'static,
+    {
+        let data = data.to_vec();
+        self.with_action(move |_| context.set_bytes(register, offset, &data))
+    }
+    /// Run a custom action when a heartbeat lapses, the action gets the client source id
+    pub fn with_action<F>(mut self, action: F) -> Self
+    where
+        F: Fn(u32) -> Result<()> + Send + Sync + 'static,
+    {
+        self.actions.push(Arc::new(action));
+        self
+    }
+    /// Set a watchdog event handler. The handler is called in the heartbeat or the checker thread
+    /// so it must not block
+    pub fn with_event_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(WatchdogEvent) + Send + Sync + 'static,
+    {
+        self.event_handler = Some(Arc::new(handler));
+        self
+    }
+    /// Process a client heartbeat, zero interval disarms the watchdog for the client. Fails if
+    /// the source is 0 (anonymous)
+    pub fn heartbeat(&self, source: u32, interval: Duration) -> Result<()> {
+        if source == 0 {
+            return Err(Error::failed("heartbeats require a non-zero source id"));
+        }
+        let event = {
+            let mut clients = self.clients.lock();
+            if interval.is_zero() {
+                clients
+                    .remove(&source)
+                    .map(|_| WatchdogEvent::Disarmed { source })
+            } else if let Some(client) = clients.get_mut(&source) {
+                client.last_heartbeat = Instant::now();
+                client.interval = interval;
+                if client.expired {
+                    client.expired = false;
+                    Some(WatchdogEvent::Recovered { source })
+                } else {
+                    None
+                }
+            } else {
+                clients.insert(
+                    source,
+                    Client {
+                        interval,
+                        last_heartbeat: Instant::now(),
+                        expired: false,
+                    },
+                );
+                Some(WatchdogEvent::Connected { source, interval })
+            }
+        };
+        if let Some(event) = event {
+            self.report(event);
+        }
+        Ok(())
+    }
+    /// Check if the client heartbeat has lapsed, `None` if the client is not watched
+    pub fn is_expired(&self, source: u32) -> Option<bool> {
+        self.clients.lock().get(&source).map(|c| c.expired)
+    }
+    /// Check all clients and run the safe-state actions for ones with lapsed heartbeats
+    pub fn check(&self) {
+        let now = Instant::now();
+        let expired: Vec<u32> = self
+            .clients
+            .lock()
+            .iter_mut()
+            .filter(|(_, c)| !c.expired && now.duration_since(c.last_heartbeat) > c.interval)
+            .map(|(source, c)| {
+                c.expired = true;
+                *source
+            })
+            .collect();
+        for source in expired {
+            tracing::warn!(source, "client heartbeat lapsed");
+            for action in &self.actions {
+                if let Err(e) = action(source) {
+                    tracing::error!(source, error = %e, "watchdog safe-state action failed");
+                }
+            }
+            self.report(WatchdogEvent::Expired { source });
+        }
+    }
+    /// Spawn a thread which checks the clients with the given period. The thread is stopped when
+    /// all watchdog instances are dropped
+    pub fn spawn_checker(&self, period: Duration) -> thread::JoinHandle<()> {
+        let clients: Weak<Mutex<BTreeMap<u32, Client>>> = Arc::downgrade(&self.clients);
+        let actions = self.actions.clone();
+        let event_handler = self.event_handler.clone();
+        thread::spawn(move || {
+            for _ in rtsc::time::interval(period) {
+                let Some(clients) = clients.upgrade() else {
+                    break;
+                };
+                Watchdog {
+                    clients,
+                    actions: actions.clone(),
+                    event_handler: event_handler.clone(),
+                }
+                .check();
+            }
+        })
+    }
+    fn report(&self, event: WatchdogEvent) {
+        if let Some(ref handler) = self.event_handler {
+            handler(event);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use std::sync::Arc;
+
+use rpdo::comm::{CommandInfo, Frame};
+use rpdo::context::Basic;
+use rpdo::host::Host;
+use rpdo::Error;
+
+#[test]
+fn typed_commands_are_called() {
+    let host = Host::new(1, Basic::new(1, 1, false))
+        .try_with_typed_command(0x8002, "scale", |_: &Frame, (value, factor): (u32, u16)| {
+            value
+                .checked_mul(u32::from(factor))
+                .ok_or_else(|| Error::try_custom(0x8100, "overflow").unwrap())
+        })
+        .unwrap()
+        .try_with_typed_command(0x8001, "source", |frame: &Frame, (): ()| Ok(frame.source))
+        .unwrap();
+    let addr = common::serve_tcp(host);
+    let mut client = common::connect(addr, 1).with_source_id(7);
+    assert_eq!(
+        client.list_commands().unwrap(),
+        [
+            CommandInfo::new(0x8001, "source"),
+            CommandInfo::new(0x8002, "scale")
+        ]
+    );
+    assert_eq!(client.call::<_, u32>(0x8001, &()).unwrap(), 7);
+    assert_eq!(client.call::<_, u32>(0x8002, &(21u32, 2u16)).unwrap(), 42);
+    let err = client
+        .call::<_, u32>(0x8002, &(u32::MAX, 2u16))
+        .unwrap_err();
+    assert!(matches!(err.kind(), Error::Custom(code, _) if code.get() == 0x8100));
+    // malformed requests are replied as errors
+    assert!(client.call::<_, u32>(0x8002, &1u8).is_err());
+    let err = client.call::<_, u32>(0x8003, &()).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidCommand));
+}
+
+#[test]
+fn invalid_registrations_are_refused() {
+    let handler = Arc::new(|_: &Frame, _: &[u8]| Ok(None));
+    let host = Host::new(1, Basic::new(1, 1, false));
+    assert!(matches!(
+        host.clone()
+            .try_with_command(0x0100, "low", handler.clone())
+            .err()
+            .unwrap()
+            .kind(),
+        Error::InvalidCommand
+    ));
+    let host = host
+        .try_with_command(0x8000, "first", handler.clone())
+        .unwrap();
+    assert!(host.try_with_command(0x8000, "second", handler).is_err());
+}
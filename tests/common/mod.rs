@@ -0,0 +1,36 @@
+#![allow(dead_code)]
+
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use rpdo::host::SyncHost;
+use rpdo::io::{SimpleClient, SimpleServerProcessor};
+
+/// Serve the host on a loopback TCP port, each connection is processed in its own thread
+pub fn serve_tcp<H>(host: H) -> SocketAddr
+where
+    H: SyncHost + Clone + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                break;
+            };
+            let mut processor = SimpleServerProcessor::new(host.clone(), stream);
+            thread::spawn(move || while processor.process_next().is_ok() {});
+        }
+    });
+    addr
+}
+
+/// Connect a client with a read timeout, so failed tests do not hang
+pub fn connect(addr: SocketAddr, target: u32) -> SimpleClient<TcpStream> {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    SimpleClient::new(stream, target)
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use rpdo::context::{Basic, Composite, RpdoContext};
+use rpdo::host::Host;
+use rpdo::Error;
+
+#[test]
+fn mounts_resolve_registers() {
+    let low = Basic::new(2, 1, false);
+    let high = Basic::new(10, 1, false);
+    let mut context = Composite::new();
+    context.mount(0..2, low.clone()).unwrap();
+    context
+        .mount_renumbered(100..=101, 8, high.clone())
+        .unwrap();
+    context.set_bytes(1, 0, &[1]).unwrap();
+    context.set_bytes(101, 0, &[2]).unwrap();
+    assert_eq!(low.get_bytes(1, 0, 1).unwrap(), [1]);
+    assert_eq!(high.get_bytes(9, 0, 1).unwrap(), [2]);
+    let addr = common::serve_tcp(Host::new(1, context));
+    let mut client = common::connect(addr, 1);
+    client.hello(0).unwrap();
+    assert_eq!(client.read_register(100, 0, 1).unwrap(), [0]);
+    assert_eq!(client.read_register(101, 0, 1).unwrap(), [2]);
+    let err = client.read_register(50, 0, 1).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidRegister));
+    assert_eq!(err.details().and_then(|d| d.register), Some(50));
+}
+
+#[test]
+fn invalid_mounts_are_refused() {
+    let mut context = Composite::new();
+    context.mount(10..20, Basic::new(20, 1, false)).unwrap();
+    assert!(context.mount(19..=25, Basic::new(30, 1, false)).is_err());
+    assert!(context.mount(5..=10, Basic::new(30, 1, false)).is_err());
+    assert!(context.mount(5..5, Basic::new(30, 1, false)).is_err());
+    assert!(matches!(
+        context
+            .mount_renumbered(0..=1, u32::MAX, Basic::new(1, 1, false))
+            .unwrap_err(),
+        Error::Overflow
+    ));
+    context.mount(20.., Basic::new(1, 1, false)).unwrap();
+    assert!(context.get_bytes(9, 0, 1).is_err());
+    assert!(context.get_bytes(20, 0, 1).is_err());
+}
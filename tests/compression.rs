@@ -0,0 +1,60 @@
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+
+use rpdo::comm::CAP_COMPRESSION;
+use rpdo::context::Basic;
+use rpdo::host::Host;
+use rpdo::io::{SimpleClient, SimpleServerProcessor, UdpStream};
+
+fn udp_client(server: SocketAddr) -> SimpleClient<UdpStream> {
+    let mut stream = UdpStream::create("127.0.0.1:0")
+        .unwrap()
+        .with_read_timeout(Duration::from_secs(2))
+        .unwrap();
+    stream.set_peer(server).unwrap();
+    SimpleClient::new(stream, 1)
+}
+
+#[test]
+fn udp_sessions_are_kept_per_peer() {
+    let context = Basic::new(2, 4096, false);
+    let host = Host::new(1, context);
+    let stream = UdpStream::create("127.0.0.1:0")
+        .unwrap()
+        .with_read_timeout(Duration::from_secs(5))
+        .unwrap();
+    let server = stream.local_addr().unwrap();
+    let mut processor = SimpleServerProcessor::new_datagram(host, stream);
+    thread::spawn(move || while processor.process_next().is_ok() {});
+
+    let mut negotiated = udp_client(server);
+    let mut plain = udp_client(server);
+    let session = negotiated.hello(CAP_COMPRESSION).unwrap();
+    assert_eq!(session.has(CAP_COMPRESSION), cfg!(feature = "compression"));
+    let data = vec![0x55; 4096];
+    negotiated.write_register(0, 0, &data).unwrap();
+    plain.write_register(1, 0, &data).unwrap();
+    for register in 0..2 {
+        assert_eq!(negotiated.read_register(register, 0, 0).unwrap(), data);
+        assert_eq!(plain.read_register(register, 0, 0).unwrap(), data);
+    }
+    assert_eq!(plain.session(), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn any_stream_can_be_served() {
+    use std::os::unix::net::UnixStream;
+
+    let (client, server) = UnixStream::pair().unwrap();
+    let mut processor =
+        SimpleServerProcessor::new(Host::new(1, Basic::new(1, 4096, false)), server);
+    thread::spawn(move || while processor.process_next().is_ok() {});
+    let mut client = SimpleClient::new(client, 1);
+    let session = client.hello(CAP_COMPRESSION).unwrap();
+    assert_eq!(session.has(CAP_COMPRESSION), cfg!(feature = "compression"));
+    let data = vec![0x55; 4096];
+    client.write_register(0, 0, &data).unwrap();
+    assert_eq!(client.read_register(0, 0, 0).unwrap(), data);
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use rpdo::context::{Basic, DirtyRange, DirtyTracked, RpdoContext};
+use rpdo::host::Host;
+use rpdo::Error;
+
+#[test]
+fn ranges_are_merged() {
+    let context = DirtyTracked::new(Basic::new(2, 16, false));
+    context.set_bytes(0, 0, &[1, 1]).unwrap();
+    context.set_bytes(0, 2, &[2]).unwrap();
+    context.set_bytes(0, 8, &[3]).unwrap();
+    context.set_bytes(1, 4, &[4, 4]).unwrap();
+    context.mark_dirty(0, 1, 0).unwrap();
+    assert_eq!(
+        context.take_dirty(),
+        [
+            DirtyRange {
+                register: 0,
+                offset: 0,
+                len: 3
+            },
+            DirtyRange {
+                register: 0,
+                offset: 8,
+                len: 1
+            },
+            DirtyRange {
+                register: 1,
+                offset: 4,
+                len: 2
+            },
+        ]
+    );
+    assert!(!context.is_dirty());
+}
+
+#[test]
+fn overflowing_ranges_are_rejected() {
+    let context = DirtyTracked::new(Basic::new(1, 4, true));
+    let err = context.mark_dirty(0, u32::MAX, 2).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidOffset));
+    let err = context.set_bytes(0, u32::MAX, &[1, 2]).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidOffset));
+    assert!(!context.is_dirty());
+    assert_eq!(context.get_bytes(0, 0, 0).unwrap(), [0; 4]);
+}
+
+#[test]
+fn changes_are_synced() {
+    let remote = Basic::new(2, 4, false);
+    let addr = common::serve_tcp(Host::new(1, remote.clone()));
+    let mut client = common::connect(addr, 1);
+    let context = DirtyTracked::new(Basic::new(2, 4, false));
+    context.set_bytes(0, 1, &[1, 2]).unwrap();
+    context.set_bytes(1, 0, &[3]).unwrap();
+    assert_eq!(context.sync_to(&mut client).unwrap(), 2);
+    assert_eq!(context.sync_to(&mut client).unwrap(), 0);
+    // the unconfirmed writes are processed in order before the read
+    assert_eq!(client.read_register(0, 0, 0).unwrap(), [0, 1, 2, 0]);
+    assert_eq!(remote.get_bytes(1, 0, 0).unwrap(), [3, 0, 0, 0]);
+}
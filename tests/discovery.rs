@@ -0,0 +1,47 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use rpdo::comm::{RegisterRange, VERSION};
+use rpdo::context::Basic;
+use rpdo::host::Host;
+use rpdo::io::{self, SimpleServerProcessor, UdpStream};
+
+fn serve_udp(host: Host<Basic>) -> SocketAddr {
+    let stream = UdpStream::create("127.0.0.1:0").unwrap();
+    let addr = stream.local_addr().unwrap();
+    let mut processor = SimpleServerProcessor::new_datagram(host, stream);
+    thread::spawn(move || while processor.process_next().is_ok() {});
+    addr
+}
+
+#[test]
+fn hosts_reply_with_info() {
+    let registers = vec![RegisterRange { first: 0, count: 4 }];
+    let host = Host::new(3, Basic::new(4, 2, false))
+        .with_info("pump", "main pump controller")
+        .with_register_summary(registers.clone());
+    let addr = serve_udp(host);
+    let replies = io::discover("127.0.0.1:0", addr, Duration::from_millis(500)).unwrap();
+    assert_eq!(replies.len(), 1);
+    let (from, reply) = &replies[0];
+    assert_eq!(*from, addr);
+    assert_eq!(reply.host_id, 3);
+    assert_eq!(reply.version, VERSION);
+    assert_eq!(reply.name, "pump");
+    assert_eq!(reply.description, "main pump controller");
+    assert_eq!(reply.registers, registers);
+}
+
+#[test]
+fn discovery_without_hosts_is_empty() {
+    // a bound socket which never replies
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let replies = io::discover(
+        "127.0.0.1:0",
+        silent.local_addr().unwrap(),
+        Duration::from_millis(100),
+    )
+    .unwrap();
+    assert!(replies.is_empty());
+}
@@ -0,0 +1,220 @@
+#![cfg(feature = "encryption")]
+
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rpdo::context::Basic;
+use rpdo::crypto::{Encrypted, Side, KEY_SIZE};
+use rpdo::host::Host;
+use rpdo::io::{SimpleClient, SimpleServerProcessor, UdpStream};
+use rpdo::Error;
+
+const KEY: [u8; KEY_SIZE] = [0x42; KEY_SIZE];
+
+type Flushed = Arc<Mutex<Vec<Vec<u8>>>>;
+
+/// Tees all written bytes, each flush is recorded separately
+struct Recorder<S> {
+    stream: S,
+    pending: Vec<u8>,
+    flushed: Flushed,
+}
+
+impl<S: Read> Read for Recorder<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl<S: Write> Write for Recorder<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.stream.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.pending.is_empty() {
+            self.flushed
+                .lock()
+                .unwrap()
+                .push(std::mem::take(&mut self.pending));
+        }
+        self.stream.flush()
+    }
+}
+
+fn recorder<S>(stream: S) -> (Recorder<S>, Flushed) {
+    let flushed = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Recorder {
+        stream,
+        pending: Vec::new(),
+        flushed: flushed.clone(),
+    };
+    (recorder, flushed)
+}
+
+/// Serve encrypted TCP connections, the result of the last processed packet of each connection
+/// is sent to the channel
+fn serve_tcp(host: Host<Basic>, key: [u8; KEY_SIZE]) -> (SocketAddr, mpsc::Receiver<Error>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                break;
+            };
+            let stream = Encrypted::create(stream, &key, Side::Server).unwrap();
+            let mut processor = SimpleServerProcessor::new(host.clone(), stream);
+            let tx = tx.clone();
+            thread::spawn(move || loop {
+                if let Err(e) = processor.process_next() {
+                    let _ = tx.send(e);
+                    break;
+                }
+            });
+        }
+    });
+    (addr, rx)
+}
+
+/// Serve encrypted datagrams, returns the server address and the dropped datagram counter
+fn serve_udp(host: Host<Basic>) -> (SocketAddr, Arc<AtomicU64>) {
+    let stream = UdpStream::create("127.0.0.1:0").unwrap();
+    let addr = stream.local_addr().unwrap();
+    let stream = Encrypted::create_datagram(stream, &KEY, Side::Server).unwrap();
+    let dropped = stream.dropped_datagrams();
+    let mut processor = SimpleServerProcessor::new_datagram(host, stream);
+    thread::spawn(move || loop {
+        let _ = processor.process_next();
+    });
+    (addr, dropped)
+}
+
+fn tcp_client(addr: SocketAddr, key: &[u8; KEY_SIZE]) -> SimpleClient<Encrypted<TcpStream>> {
+    let stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    SimpleClient::new(Encrypted::create(stream, key, Side::Client).unwrap(), 1)
+}
+
+fn udp_client(server: SocketAddr) -> SimpleClient<Encrypted<UdpStream>> {
+    SimpleClient::new(
+        Encrypted::create_datagram(udp_stream(server), &KEY, Side::Client).unwrap(),
+        1,
+    )
+}
+
+fn udp_stream(server: SocketAddr) -> UdpStream {
+    let mut stream = UdpStream::create("127.0.0.1:0")
+        .unwrap()
+        .with_read_timeout(Duration::from_secs(2))
+        .unwrap();
+    stream.set_peer(server).unwrap();
+    stream
+}
+
+#[test]
+fn tcp_roundtrip() {
+    let host = Host::new(1, Basic::new(1, 16, false));
+    let (addr, _) = serve_tcp(host, KEY);
+    let mut client = tcp_client(addr, &KEY);
+    client.ping().unwrap();
+    client.write_register(0, 0, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(client.read_register(0, 0, 4).unwrap(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn wrong_key_is_rejected() {
+    let host = Host::new(1, Basic::new(1, 16, false));
+    let (addr, errors) = serve_tcp(host, KEY);
+    let mut client = tcp_client(addr, &[0x24; KEY_SIZE]);
+    assert!(client.ping().is_err());
+    let e = errors.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert!(matches!(e, Error::Authentication), "{e:?}");
+}
+
+#[test]
+fn tcp_session_replayed_on_new_connection_is_rejected() {
+    let host = Host::new(1, Basic::new(1, 4, false));
+    let (addr, errors) = serve_tcp(host.clone(), KEY);
+    let stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let (stream, recorded) = recorder(stream);
+    let mut client = SimpleClient::new(Encrypted::create(stream, &KEY, Side::Client).unwrap(), 1);
+    client.write_register(0, 0, &[1, 1, 1, 1]).unwrap();
+    client.write_register(0, 0, &[2, 2, 2, 2]).unwrap();
+    drop(client);
+    // the connection is closed by the client
+    errors.recv_timeout(Duration::from_secs(5)).unwrap();
+    let mut replay = TcpStream::connect(addr).unwrap();
+    replay
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    // the session request and the first write
+    let recorded = recorded.lock().unwrap()[..2].concat();
+    replay.write_all(&recorded).unwrap();
+    replay.shutdown(Shutdown::Write).unwrap();
+    let mut replies = Vec::new();
+    replay.read_to_end(&mut replies).unwrap();
+    errors.recv_timeout(Duration::from_secs(5)).unwrap();
+    let mut client = tcp_client(addr, &KEY);
+    assert_eq!(client.read_register(0, 0, 4).unwrap(), [2, 2, 2, 2]);
+}
+
+#[test]
+fn udp_replayed_datagram_is_rejected() {
+    let host = Host::new(1, Basic::new(1, 4, false));
+    let (server, dropped) = serve_udp(host);
+    let (stream, recorded) = recorder(udp_stream(server));
+    let mut client = SimpleClient::new(
+        Encrypted::create_datagram(stream, &KEY, Side::Client).unwrap(),
+        1,
+    );
+    client.write_register(0, 0, &[1, 1, 1, 1]).unwrap();
+    client.write_register(0, 0, &[2, 2, 2, 2]).unwrap();
+    // the session request and two writes
+    let datagrams = recorded.lock().unwrap().clone();
+    assert_eq!(datagrams.len(), 3);
+    let attacker = UdpSocket::bind("127.0.0.1:0").unwrap();
+    attacker.send_to(&datagrams[1], server).unwrap();
+    assert_eq!(client.read_register(0, 0, 4).unwrap(), [2, 2, 2, 2]);
+    assert_eq!(dropped.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn udp_garbage_datagram_is_dropped() {
+    let host = Host::new(1, Basic::new(1, 4, false));
+    let (server, dropped) = serve_udp(host);
+    let attacker = UdpSocket::bind("127.0.0.1:0").unwrap();
+    attacker.send_to(b"garbage", server).unwrap();
+    let mut forged = b"RE".to_vec();
+    forged.resize(128, 0xAA);
+    attacker.send_to(&forged, server).unwrap();
+    let mut client = udp_client(server);
+    client.write_register(0, 0, &[4, 4, 4, 4]).unwrap();
+    assert_eq!(client.read_register(0, 0, 4).unwrap(), [4, 4, 4, 4]);
+    assert_eq!(dropped.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn forgotten_udp_session_is_re_established() {
+    let host = Host::new(1, Basic::new(1, 4, false));
+    let (server, _) = serve_udp(host);
+    let mut first = udp_client(server);
+    first.ping().unwrap();
+    // the server keeps a limited number of client sessions
+    for _ in 0..64 {
+        udp_client(server).ping().unwrap();
+    }
+    let e = first.ping().unwrap_err();
+    assert!(matches!(e, Error::Authentication), "{e:?}");
+    first.write_register(0, 0, &[3, 3, 3, 3]).unwrap();
+    assert_eq!(first.read_register(0, 0, 4).unwrap(), [3, 3, 3, 3]);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::Host;
+use rpdo::{Error, ErrorDetails, UserErrorCode, ERR_USER_MIN};
+
+#[test]
+fn details_roundtrip() {
+    let err = Error::InvalidOffset.with_details(ErrorDetails {
+        register: Some(3),
+        offset: Some(8),
+        expected_size: Some(16),
+        actual_size: Some(24),
+    });
+    let decoded = Error::from(Vec::<u8>::from(err).as_slice());
+    assert!(matches!(decoded.kind(), Error::InvalidOffset));
+    assert_eq!(
+        decoded.details(),
+        Some(&ErrorDetails {
+            register: Some(3),
+            offset: Some(8),
+            expected_size: Some(16),
+            actual_size: Some(24),
+        })
+    );
+    let decoded = Error::from(Vec::<u8>::from(Error::validation("too large")).as_slice());
+    assert!(matches!(decoded, Error::Validation(ref m) if m == "too large"));
+}
+
+#[test]
+fn custom_codes_are_checked() {
+    let err = Error::try_custom(0x8123, "busy").unwrap();
+    assert_eq!(err.code(), 0x8123);
+    let decoded = Error::from(Vec::<u8>::from(err).as_slice());
+    assert!(matches!(decoded, Error::Custom(code, ref m) if code.get() == 0x8123 && m == "busy"));
+    assert!(Error::try_custom(0x0003, "busy").is_err());
+    assert!(Error::try_custom(ERR_USER_MIN - 1, "busy").is_err());
+    assert!(UserErrorCode::new(ERR_USER_MIN).is_some());
+}
+
+#[test]
+fn local_errors_are_plain() {
+    let context = Basic::new(1, 4, false);
+    assert!(matches!(
+        context.get_bytes(0, 2, 4).unwrap_err(),
+        Error::InvalidOffset
+    ));
+    assert!(matches!(
+        context.get_bytes(5, 0, 0).unwrap_err(),
+        Error::InvalidRegister
+    ));
+}
+
+#[test]
+fn remote_errors_carry_request_details() {
+    let addr = common::serve_tcp(Host::new(1, Basic::new(1, 4, false)));
+    let mut client = common::connect(addr, 1);
+    client.hello(0).unwrap();
+    let err = client.read_register(5, 0, 0).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidRegister));
+    assert_eq!(err.details().and_then(|d| d.register), Some(5));
+    let err = client.read_register(0, 2, 4).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidOffset));
+    assert_eq!(
+        err.details(),
+        Some(&ErrorDetails {
+            register: Some(0),
+            offset: Some(2),
+            expected_size: None,
+            actual_size: Some(6),
+        })
+    );
+}
+
+#[test]
+fn version_0_peers_receive_plain_errors() {
+    let addr = common::serve_tcp(Host::new(1, Basic::new(1, 4, false)));
+    let mut client = common::connect(addr, 1);
+    let err = client.read_register(5, 0, 0).unwrap_err();
+    assert!(matches!(err, Error::InvalidRegister), "{err:?}");
+}
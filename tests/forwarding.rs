@@ -0,0 +1,128 @@
+mod common;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use rpdo::comm::{HistorySample, Quality, RegisterMetadata};
+use rpdo::context::{
+    Basic, Composite, DirtyTracked, History, Qualified, Remote, RpdoContext, Validated, Versioned,
+    Watched,
+};
+use rpdo::host::Host;
+
+/// Implements all context methods with distinctive results
+#[derive(Clone, Default)]
+struct Probe {
+    source: Arc<AtomicU32>,
+}
+
+impl RpdoContext for Probe {
+    fn get_bytes(&self, _register: u32, _offset: u32, _data_size: u32) -> rpdo::Result<Vec<u8>> {
+        Ok(vec![0])
+    }
+    fn set_bytes(&self, _register: u32, _offset: u32, _data: &[u8]) -> rpdo::Result<()> {
+        self.source.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+    fn set_bytes_from(
+        &self,
+        source: u32,
+        _register: u32,
+        _offset: u32,
+        _data: &[u8],
+    ) -> rpdo::Result<()> {
+        self.source.store(source, Ordering::SeqCst);
+        Ok(())
+    }
+    fn get_bytes_with_metadata(
+        &self,
+        _register: u32,
+        _offset: u32,
+        _data_size: u32,
+        _since_version: u64,
+    ) -> rpdo::Result<(RegisterMetadata, Option<Vec<u8>>)> {
+        let metadata = RegisterMetadata {
+            version: 42,
+            ..RegisterMetadata::default()
+        };
+        Ok((metadata, Some(vec![0])))
+    }
+    fn get_history(
+        &self,
+        _register: u32,
+        _from: u64,
+        _to: u64,
+        _max_samples: u32,
+    ) -> rpdo::Result<Vec<HistorySample>> {
+        Ok(vec![HistorySample::new(42, vec![0])])
+    }
+    fn get_bytes_with_quality(
+        &self,
+        _register: u32,
+        _offset: u32,
+        _data_size: u32,
+    ) -> rpdo::Result<(Quality, Vec<u8>)> {
+        Ok((Quality::Uncertain, vec![0]))
+    }
+}
+
+/// The methods a wrapper implements itself
+#[derive(Default)]
+struct Overrides {
+    metadata: bool,
+    history: bool,
+    quality: bool,
+}
+
+fn assert_forwarded<C: RpdoContext>(context: &C, probe: &Probe, overrides: &Overrides) {
+    context.set_bytes_from(7, 0, 0, &[1]).unwrap();
+    assert_eq!(probe.source.load(Ordering::SeqCst), 7);
+    if !overrides.metadata {
+        let (metadata, _) = context.get_bytes_with_metadata(0, 0, 1, 0).unwrap();
+        assert_eq!(metadata.version, 42);
+    }
+    if !overrides.history {
+        let samples = context.get_history(0, 0, u64::MAX, 0).unwrap();
+        assert_eq!(samples, [HistorySample::new(42, vec![0])]);
+    }
+    if !overrides.quality {
+        let (quality, _) = context.get_bytes_with_quality(0, 0, 1).unwrap();
+        assert_eq!(quality, Quality::Uncertain);
+    }
+}
+
+#[test]
+fn wrappers_forward_all_methods() {
+    let probe = Probe::default();
+    let all = Overrides::default();
+    assert_forwarded(&Watched::new(probe.clone()), &probe, &all);
+    assert_forwarded(&Validated::new(probe.clone()), &probe, &all);
+    assert_forwarded(&DirtyTracked::new(probe.clone()), &probe, &all);
+    let versioned = Overrides {
+        metadata: true,
+        ..Overrides::default()
+    };
+    assert_forwarded(&Versioned::new(probe.clone()), &probe, &versioned);
+    let history = Overrides {
+        history: true,
+        ..Overrides::default()
+    };
+    assert_forwarded(&History::new(probe.clone()), &probe, &history);
+    let qualified = Overrides {
+        quality: true,
+        ..Overrides::default()
+    };
+    assert_forwarded(&Qualified::new(probe.clone()), &probe, &qualified);
+    let mut composite = Composite::new();
+    composite.mount(0..1, probe.clone()).unwrap();
+    composite.mount(1..2, Basic::new(1, 1, false)).unwrap();
+    assert_forwarded(&composite, &probe, &all);
+}
+
+#[test]
+fn remote_forwards_all_methods() {
+    let probe = Probe::default();
+    let addr = common::serve_tcp(Host::new(2, probe.clone()));
+    let remote = Remote::new(common::connect(addr, 2));
+    assert_forwarded(&remote, &probe, &Overrides::default());
+}
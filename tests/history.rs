@@ -0,0 +1,37 @@
+mod common;
+
+use rpdo::context::{Basic, History, Recording, RpdoContext};
+use rpdo::host::Host;
+
+#[test]
+fn failed_registers_do_not_stop_sampling() {
+    let context = History::new(Basic::new(2, 1, false));
+    context.record(0, 4, Recording::Sampled).unwrap();
+    // not present in the inner context
+    context.record(1000, 4, Recording::Sampled).unwrap();
+    context.record(1, 4, Recording::Sampled).unwrap();
+    context.set_bytes(0, 0, &[1]).unwrap();
+    context.set_bytes(1, 0, &[2]).unwrap();
+    assert!(context.sample().is_err());
+    assert_eq!(context.history(0, 0, u64::MAX, 0).unwrap().len(), 1);
+    assert_eq!(context.history(1, 0, u64::MAX, 0).unwrap()[0].data, [2]);
+    assert!(context.history(1000, 0, u64::MAX, 0).unwrap().is_empty());
+}
+
+#[test]
+fn history_is_read_remotely() {
+    let context = History::new(Basic::new(1, 1, false));
+    context.record(0, 2, Recording::OnWrite).unwrap();
+    let addr = common::serve_tcp(Host::new(1, context.clone()));
+    let mut client = common::connect(addr, 1);
+    for value in 1..=3 {
+        client.write_register(0, 0, &[value]).unwrap();
+    }
+    let samples = client.read_history(0, 0, u64::MAX, 0).unwrap();
+    let values: Vec<u8> = samples.iter().map(|s| s.data[0]).collect();
+    assert_eq!(values, [2, 3]);
+    assert!(samples[0].timestamp <= samples[1].timestamp);
+    let samples = client.read_history(0, 0, u64::MAX, 1).unwrap();
+    assert_eq!(samples.len(), 1);
+    assert!(client.read_history(1, 0, u64::MAX, 0).is_err());
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use rpdo::context::{ProcessImage, RpdoContext};
+use rpdo::host::Host;
+use rpdo::Error;
+
+#[test]
+fn remote_writes_are_applied_at_commit() {
+    let image = ProcessImage::new(2, 4, false);
+    let addr = common::serve_tcp(Host::new(1, image.clone()));
+    let mut client = common::connect(addr, 1);
+    client.write_register(0, 0, &[1, 2]).unwrap();
+    client.write_register(0, 2, &[3, 4]).unwrap();
+    assert_eq!(image.staged_count(), 2);
+    // the cycle inputs are frozen until the commit
+    assert_eq!(image.get_logic_bytes(0, 0, 4).unwrap(), [0, 0, 0, 0]);
+    assert_eq!(client.read_register(0, 0, 4).unwrap(), [0, 0, 0, 0]);
+    assert_eq!(image.commit().unwrap(), 2);
+    assert_eq!(image.staged_count(), 0);
+    assert_eq!(image.get_logic_bytes(0, 0, 4).unwrap(), [1, 2, 3, 4]);
+    assert_eq!(client.read_register(0, 0, 4).unwrap(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn logic_writes_are_published_at_commit() {
+    let image = ProcessImage::new(2, 4, false);
+    image.set::<u32>(1, 0, &0x0403_0201).unwrap();
+    assert_eq!(image.get::<u32>(1, 0, 4).unwrap(), 0x0403_0201);
+    assert_eq!(image.get_bytes(1, 0, 4).unwrap(), [0, 0, 0, 0]);
+    assert_eq!(image.commit().unwrap(), 0);
+    assert_eq!(image.get_bytes(1, 0, 4).unwrap(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn invalid_writes_are_refused_before_staging() {
+    let image = ProcessImage::new(1, 2, false);
+    assert!(image.set_bytes(1, 0, &[1]).is_err());
+    assert!(image.set_bytes(0, 1, &[1, 2]).is_err());
+    assert!(image.set_logic_bytes(0, 2, &[1]).is_err());
+    assert_eq!(image.staged_count(), 0);
+}
+
+#[test]
+fn staged_writes_are_bounded() {
+    let image = ProcessImage::new(2, 4, false).with_max_staged(2);
+    image.set_bytes(0, 0, &[1, 1]).unwrap();
+    image.set_bytes(1, 0, &[2, 2]).unwrap();
+    assert!(matches!(
+        image.set_bytes(0, 2, &[3, 3]).unwrap_err(),
+        Error::Overflow
+    ));
+    // the last staged write of the register is replaced
+    image.set_bytes(1, 0, &[4, 4]).unwrap();
+    assert_eq!(image.staged_count(), 2);
+    assert_eq!(image.commit().unwrap(), 2);
+    assert_eq!(image.get_bytes(0, 0, 4).unwrap(), [1, 1, 0, 0]);
+    assert_eq!(image.get_bytes(1, 0, 4).unwrap(), [4, 4, 0, 0]);
+    image.set_bytes(0, 2, &[3, 3]).unwrap();
+}
+
+#[test]
+fn buffers_stay_consistent_across_commits() {
+    let image = ProcessImage::new(2, 4, true);
+    image.set_logic_bytes(0, 0, &[1]).unwrap();
+    image.set_bytes(1, 2, &[2, 2, 2, 2]).unwrap();
+    image.commit().unwrap();
+    image.set_logic_bytes(0, 3, &[3]).unwrap();
+    image.commit().unwrap();
+    for register in 0..2 {
+        assert_eq!(
+            image.get_logic_bytes(register, 0, 0).unwrap(),
+            image.get_bytes(register, 0, 0).unwrap()
+        );
+    }
+    assert_eq!(image.get_bytes(0, 0, 4).unwrap(), [1, 0, 0, 3]);
+    assert_eq!(image.get_bytes(1, 0, 0).unwrap(), [0, 0, 2, 2, 2, 2]);
+}
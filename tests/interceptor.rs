@@ -0,0 +1,202 @@
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use binrw::prelude::*;
+use rpdo::comm::{Command, DeadlineHeader, Frame, RawDataHeader};
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::{Host, Interceptor, ProcessResult, SyncHost};
+
+struct Recorder {
+    name: &'static str,
+    log: Arc<Mutex<Vec<String>>>,
+}
+
+impl Interceptor for Recorder {
+    fn before(&self, frame: &Frame, _data: &[u8]) -> rpdo::Result<()> {
+        self.log
+            .lock()
+            .unwrap()
+            .push(format!("{} before {:?}", self.name, frame.command));
+        Ok(())
+    }
+    fn after(&self, frame: &Frame, _data: &[u8], result: &mut ProcessResult) {
+        self.log.lock().unwrap().push(format!(
+            "{} after {:?} {}",
+            self.name,
+            frame.command,
+            if result.is_ok() { "ok" } else { "err" }
+        ));
+    }
+}
+
+/// Denies shared context writes
+struct ReadOnly;
+
+impl Interceptor for ReadOnly {
+    fn before(&self, frame: &Frame, _data: &[u8]) -> rpdo::Result<()> {
+        if matches!(
+            frame.command,
+            Command::WriteSharedContext | Command::WriteSharedContextUnconfirmed
+        ) {
+            return Err(rpdo::Error::failed("read-only"));
+        }
+        Ok(())
+    }
+}
+
+/// Reverses read replies
+struct Reverse;
+
+impl Interceptor for Reverse {
+    fn after(&self, frame: &Frame, _data: &[u8], result: &mut ProcessResult) {
+        if frame.command == Command::ReadSharedContext {
+            if let Ok(Some((_, data))) = result {
+                data.reverse();
+            }
+        }
+    }
+}
+
+fn request(command: Command) -> Frame {
+    Frame {
+        source: 10,
+        target: 1,
+        id: 1,
+        in_reply_to: 0,
+        command,
+    }
+}
+
+fn write_data(register: u32, data: &[u8]) -> Vec<u8> {
+    let mut buf = Cursor::new(Vec::new());
+    RawDataHeader {
+        register,
+        offset: 0,
+        size: u32::try_from(data.len()).unwrap(),
+    }
+    .write(&mut buf)
+    .unwrap();
+    buf.get_mut().extend(data);
+    buf.into_inner()
+}
+
+fn with_deadline(command: Command, data: &[u8]) -> Vec<u8> {
+    let mut buf = Cursor::new(Vec::new());
+    DeadlineHeader::new(SystemTime::now() + Duration::from_secs(10), command)
+        .write(&mut buf)
+        .unwrap();
+    buf.get_mut().extend(data);
+    buf.into_inner()
+}
+
+#[test]
+fn hooks_are_called_in_order() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let host = Host::new(1, Basic::new(1, 4, false))
+        .with_interceptor(Arc::new(Recorder {
+            name: "a",
+            log: log.clone(),
+        }))
+        .with_interceptor(Arc::new(Recorder {
+            name: "b",
+            log: log.clone(),
+        }));
+    host.process_frame(&request(Command::Ping), &[]).unwrap();
+    // failed processing is passed to the after hooks as well
+    assert!(host
+        .process_frame(&request(Command::ReadSharedContext), &[0])
+        .is_err());
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            "a before Ping",
+            "b before Ping",
+            "b after Ping ok",
+            "a after Ping ok",
+            "a before ReadSharedContext",
+            "b before ReadSharedContext",
+            "b after ReadSharedContext err",
+            "a after ReadSharedContext err",
+        ]
+    );
+}
+
+#[test]
+fn failed_before_hook_short_circuits() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let context = Basic::new(1, 4, false);
+    let host = Host::new(1, context.clone())
+        .with_interceptor(Arc::new(ReadOnly))
+        .with_interceptor(Arc::new(Recorder {
+            name: "a",
+            log: log.clone(),
+        }));
+    let (reply, data) = host
+        .process_frame(
+            &request(Command::WriteSharedContext),
+            &write_data(0, &[1, 2, 3, 4]),
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply.command, Command::Error);
+    assert!(
+        matches!(rpdo::Error::from(data.as_slice()), rpdo::Error::Failed(m) if m == "read-only")
+    );
+    // frames with no replies are dropped
+    assert!(host
+        .process_frame(
+            &request(Command::WriteSharedContextUnconfirmed),
+            &write_data(0, &[1, 2, 3, 4]),
+        )
+        .unwrap()
+        .is_none());
+    assert_eq!(context.get_bytes(0, 0, 4).unwrap(), [0; 4]);
+    assert!(log.lock().unwrap().is_empty());
+}
+
+#[test]
+fn deadline_envelope_does_not_bypass_interceptors() {
+    let context = Basic::new(1, 4, false);
+    let host = Host::new(1, context.clone()).with_interceptor(Arc::new(ReadOnly));
+    let (reply, _) = host
+        .process_frame(
+            &request(Command::Deadline),
+            &with_deadline(Command::WriteSharedContext, &write_data(0, &[1, 2, 3, 4])),
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply.command, Command::Error);
+    assert!(host
+        .process_frame(
+            &request(Command::Deadline),
+            &with_deadline(
+                Command::WriteSharedContextUnconfirmed,
+                &write_data(0, &[1, 2, 3, 4])
+            ),
+        )
+        .unwrap()
+        .is_none());
+    assert_eq!(context.get_bytes(0, 0, 4).unwrap(), [0; 4]);
+}
+
+#[test]
+fn after_hook_modifies_reply() {
+    let context = Basic::new(1, 4, false);
+    context.set_bytes(0, 0, &[1, 2, 3, 4]).unwrap();
+    let host = Host::new(1, context).with_interceptor(Arc::new(Reverse));
+    let mut buf = Cursor::new(Vec::new());
+    RawDataHeader {
+        register: 0,
+        offset: 0,
+        size: 4,
+    }
+    .write(&mut buf)
+    .unwrap();
+    let (reply, data) = host
+        .process_frame(&request(Command::ReadSharedContext), buf.get_ref())
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply.command, Command::Reply);
+    assert_eq!(data, [4, 3, 2, 1]);
+}
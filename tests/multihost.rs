@@ -0,0 +1,34 @@
+mod common;
+
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::{Host, MultiHost};
+use rpdo::Error;
+
+#[test]
+fn frames_are_dispatched_by_target() {
+    let first = Basic::new(1, 1, false);
+    let second = Basic::new(1, 1, false);
+    let host = MultiHost::new()
+        .try_with_host(1, Host::new(1, first.clone()))
+        .unwrap()
+        .try_with_host(2, Host::new(2, second.clone()))
+        .unwrap()
+        .with_default_host(2);
+    let addr = common::serve_tcp(host);
+    common::connect(addr, 1).write_register(0, 0, &[1]).unwrap();
+    common::connect(addr, 0).write_register(0, 0, &[2]).unwrap();
+    assert_eq!(first.get_bytes(0, 0, 0).unwrap(), [1]);
+    assert_eq!(second.get_bytes(0, 0, 0).unwrap(), [2]);
+    assert!(common::connect(addr, 3).ping().is_err());
+}
+
+#[test]
+fn mismatched_host_id_is_refused() {
+    let Err(err) = MultiHost::new().try_with_host(2, Host::new(1, Basic::new(1, 1, false))) else {
+        panic!("the mismatched host is accepted");
+    };
+    assert!(matches!(err, Error::Failed(ref m) if m == "the host id does not match 2"));
+    assert!(MultiHost::new()
+        .try_with_host(0, Host::new(0, Basic::new(1, 1, false)))
+        .is_err());
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::io::Cursor;
+
+use binrw::BinWrite;
+use rpdo::comm::{
+    Command, Frame, Hello, HistoryReadHeader, Packet, CAP_COMPRESSION, MIN_VERSION, VERSION,
+};
+use rpdo::context::Basic;
+use rpdo::host::Host;
+use rpdo::Error;
+
+fn frame() -> Frame {
+    Frame {
+        source: 1,
+        target: 2,
+        id: 3,
+        in_reply_to: 0,
+        command: Command::Ping,
+    }
+}
+
+#[test]
+fn packet_keeps_version() {
+    let mut buf = Vec::new();
+    Packet::new(frame(), 0)
+        .with_version(MIN_VERSION)
+        .write_to(&mut buf)
+        .unwrap();
+    let packet = Packet::read_from(&mut Cursor::new(&buf)).unwrap();
+    assert_eq!(packet.version(), MIN_VERSION);
+    assert_eq!(packet.frame().id, 3);
+}
+
+#[test]
+fn unsupported_version_is_rejected() {
+    let mut buf = Vec::new();
+    Packet::new(frame(), 0)
+        .with_version(VERSION + 1)
+        .write_to(&mut buf)
+        .unwrap();
+    let result = Packet::read_from(&mut Cursor::new(&buf));
+    assert!(matches!(result, Err(Error::UnsupportedVersion)));
+}
+
+#[test]
+fn hello_negotiates_session_version() {
+    let addr = common::serve_tcp(Host::new(1, Basic::new(1, 4, false)));
+    let mut client = common::connect(addr, 1);
+    let session = client.hello(0).unwrap();
+    assert_eq!(session.version, VERSION);
+    assert_eq!(client.session(), Some(session));
+    client.write_register(0, 0, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(client.read_register(0, 0, 0).unwrap(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn hello_agrees_on_common_version() {
+    let old = Hello {
+        min_version: MIN_VERSION,
+        max_version: MIN_VERSION,
+        capabilities: CAP_COMPRESSION,
+    };
+    let session = old.negotiate(CAP_COMPRESSION).unwrap();
+    assert_eq!(session.version, MIN_VERSION);
+    let future = Hello {
+        min_version: VERSION + 1,
+        max_version: VERSION + 2,
+        capabilities: 0,
+    };
+    assert!(matches!(
+        future.negotiate(0),
+        Err(Error::UnsupportedVersion)
+    ));
+    assert_eq!(Hello::new(0).negotiate(0).unwrap().version, VERSION);
+}
+
+#[test]
+fn history_header_size() {
+    let mut buf = Cursor::new(Vec::new());
+    HistoryReadHeader {
+        register: 1,
+        from: 2,
+        to: 3,
+        max_samples: 4,
+    }
+    .write_le(&mut buf)
+    .unwrap();
+    assert_eq!(buf.get_ref().len(), HistoryReadHeader::SIZE);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use rpdo::comm::Quality;
+use rpdo::context::{Basic, Qualified, RpdoContext};
+use rpdo::host::Host;
+
+#[test]
+fn quality_is_reported_with_data() {
+    let context = Qualified::new(Basic::new(2, 2, false));
+    let addr = common::serve_tcp(Host::new(1, context.clone()));
+    let mut client = common::connect(addr, 1);
+    client.write_register(0, 0, &[1, 2]).unwrap();
+    assert_eq!(
+        client.read_register_with_quality(0, 0, 2).unwrap(),
+        (Quality::Good, vec![1, 2])
+    );
+    context.set_quality(1, Quality::Uncertain);
+    assert_eq!(
+        client.read_register_with_quality(1, 0, 2).unwrap(),
+        (Quality::Uncertain, vec![0, 0])
+    );
+    assert!(client.read_register_with_quality(2, 0, 2).is_err());
+}
+
+#[test]
+fn registers_without_writes_become_stale() {
+    let context =
+        Qualified::new(Basic::new(2, 1, false)).with_stale_after(Duration::from_millis(50));
+    context.set_bytes(0, 0, &[1]).unwrap();
+    context.set_quality(1, Quality::Bad);
+    assert_eq!(context.quality(0), Quality::Good);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(context.quality(0), Quality::Stale);
+    // bad values are reported as bad even if stale
+    assert_eq!(context.quality(1), Quality::Bad);
+    context.set_bytes(0, 0, &[2]).unwrap();
+    assert_eq!(
+        context.get_bytes_with_quality(0, 0, 1).unwrap(),
+        (Quality::Good, vec![2])
+    );
+}
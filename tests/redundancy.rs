@@ -0,0 +1,147 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::Host;
+use rpdo::io::{SimpleClient, SimpleServerProcessor};
+use rpdo::redundancy::{Monitor, Redundancy, Role};
+
+struct Node {
+    context: Basic,
+    redundancy: Redundancy,
+    addr: SocketAddr,
+    online: Arc<AtomicBool>,
+}
+
+impl Node {
+    /// Serve a host on loopback, `online` simulates the host power/network state
+    fn start(id: u32, role: Role) -> Self {
+        let context = Basic::new(4, 4, false);
+        let redundancy = Redundancy::new(role);
+        let host = Host::new(id, context.clone()).with_redundancy(redundancy.clone());
+        let online = Arc::new(AtomicBool::new(true));
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node_online = online.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                if !node_online.load(Ordering::Acquire) {
+                    continue;
+                }
+                stream
+                    .set_read_timeout(Some(Duration::from_millis(20)))
+                    .unwrap();
+                let mut processor = SimpleServerProcessor::new(host.clone(), stream);
+                let online = node_online.clone();
+                thread::spawn(move || {
+                    while online.load(Ordering::Acquire) {
+                        if let Err(rpdo::Error::Io(e)) = processor.process_next() {
+                            if !matches!(
+                                e.kind(),
+                                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                            ) {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        Self {
+            context,
+            redundancy,
+            addr,
+            online,
+        }
+    }
+    fn client(&self, target: u32) -> rpdo::Result<SimpleClient<TcpStream>> {
+        if !self.online.load(Ordering::Acquire) {
+            return Err(rpdo::Error::Io(
+                std::io::ErrorKind::ConnectionRefused.into(),
+            ));
+        }
+        let stream = TcpStream::connect(self.addr)?;
+        stream.set_read_timeout(Some(Duration::from_millis(500)))?;
+        Ok(SimpleClient::new(stream, target))
+    }
+    fn monitor(&self, peer: &Node, peer_id: u32) -> Monitor<Basic, TcpStream> {
+        let peer_addr = peer.addr;
+        let peer_online = peer.online.clone();
+        Monitor::new(self.redundancy.clone(), self.context.clone(), move || {
+            if !peer_online.load(Ordering::Acquire) {
+                return Err(rpdo::Error::Io(
+                    std::io::ErrorKind::ConnectionRefused.into(),
+                ));
+            }
+            let stream = TcpStream::connect(peer_addr)?;
+            stream.set_read_timeout(Some(Duration::from_millis(500)))?;
+            Ok(SimpleClient::new(stream, peer_id))
+        })
+        .with_max_failures(3)
+    }
+}
+
+#[test]
+fn standby_replicates_takes_over_and_demotes_recovered_primary() {
+    let primary = Node::start(1, Role::Primary);
+    let standby = Node::start(2, Role::Standby);
+    let mut primary_monitor = primary.monitor(&standby, 2);
+    let mut standby_monitor = standby.monitor(&primary, 1).with_registers(0..4);
+
+    // replication
+    let mut client = primary.client(1).unwrap();
+    client.write_register(0, 0, &42u32.to_le_bytes()).unwrap();
+    standby_monitor.step().unwrap();
+    primary_monitor.step().unwrap();
+    assert_eq!(standby.context.get::<u32>(0, 0, 4).unwrap(), 42);
+    assert_eq!(standby.redundancy.epoch(), 1);
+
+    // the standby rejects client writes
+    let mut standby_client = standby.client(2).unwrap();
+    assert!(matches!(
+        standby_client.write_register(0, 0, &[1]),
+        Err(rpdo::Error::Standby)
+    ));
+
+    // takeover after max_failures failed checks in a row
+    primary.online.store(false, Ordering::Release);
+    thread::sleep(Duration::from_millis(100));
+    for _ in 0..2 {
+        assert!(standby_monitor.step().is_err());
+        assert_eq!(standby.redundancy.role(), Role::Standby);
+    }
+    assert!(standby_monitor.step().is_err());
+    assert_eq!(standby.redundancy.role(), Role::Primary);
+    assert_eq!(standby.redundancy.epoch(), 2);
+    standby_client
+        .write_register(0, 0, &43u32.to_le_bytes())
+        .unwrap();
+
+    // the recovered primary demotes itself and rejects writes
+    primary.online.store(true, Ordering::Release);
+    primary_monitor.step().unwrap();
+    assert_eq!(primary.redundancy.role(), Role::Standby);
+    assert_eq!(primary.redundancy.epoch(), 2);
+    let mut client = primary.client(1).unwrap();
+    assert!(matches!(
+        client.write_register(0, 0, &[1]),
+        Err(rpdo::Error::Standby)
+    ));
+}
+
+#[test]
+fn replication_errors_are_not_counted_as_failures() {
+    let primary = Node::start(1, Role::Primary);
+    let standby = Node::start(2, Role::Standby);
+    // the primary has 4 registers only
+    let mut standby_monitor = standby.monitor(&primary, 1).with_registers(0..10);
+    for _ in 0..5 {
+        assert!(standby_monitor.step().is_err());
+    }
+    assert_eq!(standby.redundancy.role(), Role::Standby);
+    assert_eq!(standby.context.get_bytes(0, 0, 4).unwrap(), [0; 4]);
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rpdo::context::{Basic, Composite, Remote, RpdoContext, Versioned};
+use rpdo::host::Host;
+use rpdo::Error;
+
+/// Blocks reads after the data is taken while the gate is locked
+#[derive(Clone)]
+struct Gated {
+    context: Basic,
+    gate: Arc<Mutex<()>>,
+    entered: Arc<AtomicBool>,
+}
+
+impl RpdoContext for Gated {
+    fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> rpdo::Result<Vec<u8>> {
+        let data = self.context.get_bytes(register, offset, data_size);
+        self.entered.store(true, Ordering::SeqCst);
+        let _gate = self.gate.lock().unwrap();
+        data
+    }
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> rpdo::Result<()> {
+        self.context.set_bytes(register, offset, data)
+    }
+}
+
+#[test]
+fn requests_are_forwarded() {
+    let downstream = Basic::new(2, 2, false);
+    let addr = common::serve_tcp(Host::new(2, downstream.clone()));
+    let remote = Remote::new(common::connect(addr, 2));
+    // republish the downstream device
+    let mut context = Composite::new();
+    context.mount(.., remote).unwrap();
+    let addr = common::serve_tcp(Host::new(1, context));
+    let mut client = common::connect(addr, 1);
+    client.write_register(1, 0, &[1, 2]).unwrap();
+    assert_eq!(downstream.get_bytes(1, 0, 2).unwrap(), [1, 2]);
+    downstream.set_bytes(0, 0, &[3, 4]).unwrap();
+    assert_eq!(client.read_register(0, 0, 2).unwrap(), [3, 4]);
+    let err = client.read_register(2, 0, 2).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidRegister));
+}
+
+#[test]
+fn reads_are_cached() {
+    let downstream = Basic::new(2, 1, false);
+    let addr = common::serve_tcp(Host::new(2, downstream.clone()));
+    let remote = Remote::new(common::connect(addr, 2)).with_cache_ttl(Duration::from_secs(60));
+    downstream.set_bytes(0, 0, &[1]).unwrap();
+    downstream.set_bytes(1, 0, &[1]).unwrap();
+    assert_eq!(remote.get_bytes(0, 0, 1).unwrap(), [1]);
+    assert_eq!(remote.get_bytes(1, 0, 1).unwrap(), [1]);
+    downstream.set_bytes(0, 0, &[2]).unwrap();
+    downstream.set_bytes(1, 0, &[2]).unwrap();
+    assert_eq!(remote.get_bytes(0, 0, 1).unwrap(), [1]);
+    // writes invalidate the register cache only
+    remote.set_bytes(0, 0, &[3]).unwrap();
+    assert_eq!(remote.get_bytes(0, 0, 1).unwrap(), [3]);
+    assert_eq!(remote.get_bytes(1, 0, 1).unwrap(), [1]);
+    remote.clear_cache();
+    assert_eq!(remote.get_bytes(1, 0, 1).unwrap(), [2]);
+}
+
+#[test]
+fn reads_overlapping_invalidation_are_not_cached() {
+    let downstream = Gated {
+        context: Basic::new(1, 1, false),
+        gate: <_>::default(),
+        entered: <_>::default(),
+    };
+    downstream.set_bytes(0, 0, &[1]).unwrap();
+    let addr = common::serve_tcp(Host::new(2, downstream.clone()));
+    let remote =
+        Arc::new(Remote::new(common::connect(addr, 2)).with_cache_ttl(Duration::from_secs(60)));
+    let gate = downstream.gate.lock().unwrap();
+    let reader = thread::spawn({
+        let remote = remote.clone();
+        move || remote.get_bytes(0, 0, 1).unwrap()
+    });
+    while !downstream.entered.load(Ordering::SeqCst) {
+        thread::yield_now();
+    }
+    downstream.set_bytes(0, 0, &[2]).unwrap();
+    remote.clear_cache();
+    drop(gate);
+    assert_eq!(reader.join().unwrap(), [1]);
+    assert_eq!(remote.get_bytes(0, 0, 1).unwrap(), [2]);
+}
+
+#[test]
+fn writes_keep_the_source() {
+    let downstream = Versioned::new(Basic::new(1, 1, false));
+    let addr = common::serve_tcp(Host::new(2, downstream.clone()));
+    let mut context = Composite::new();
+    context
+        .mount(.., Remote::new(common::connect(addr, 2)))
+        .unwrap();
+    let addr = common::serve_tcp(Host::new(1, context));
+    let mut client = common::connect(addr, 1).with_source_id(9);
+    client.write_register(0, 0, &[1]).unwrap();
+    assert_eq!(downstream.metadata(0).source, 9);
+}
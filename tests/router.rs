@@ -0,0 +1,131 @@
+mod common;
+
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use rpdo::comm::{Command, Frame};
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::{CustomCommandHandler, Host};
+use rpdo::router::{Downstream, Router};
+use rpdo::Error;
+
+/// Counts custom commands, no replies are sent
+struct Counter(Arc<AtomicUsize>);
+
+impl CustomCommandHandler for Counter {
+    fn handle(&self, _frame: &Frame, _data: &[u8]) -> rpdo::Result<Option<Vec<u8>>> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(None)
+    }
+}
+
+#[test]
+fn frames_are_routed_by_target() {
+    let local = Basic::new(1, 1, false);
+    let remote = Basic::new(1, 1, false);
+    let downstream_addr = common::serve_tcp(Host::new(5, remote.clone()));
+    let router = Router::new(Host::new(1, local.clone())).with_route(
+        5,
+        Downstream::new(TcpStream::connect(downstream_addr).unwrap()),
+    );
+    assert!(router.has_route(5));
+    assert!(!router.has_route(1));
+    let addr = common::serve_tcp(router);
+    let mut client = common::connect(addr, 5);
+    client.write_register(0, 0, &[5]).unwrap();
+    assert_eq!(remote.get_bytes(0, 0, 1).unwrap(), [5]);
+    let mut client = common::connect(addr, 1);
+    client.write_register(0, 0, &[1]).unwrap();
+    assert_eq!(local.get_bytes(0, 0, 1).unwrap(), [1]);
+    let mut client = common::connect(addr, 9);
+    let err = client.ping().unwrap_err();
+    assert!(matches!(err.kind(), Error::UnknownHost));
+}
+
+#[test]
+fn connector_reconnects_after_failures() {
+    let remote = Basic::new(1, 1, false);
+    let downstream_addr = common::serve_tcp(Host::new(5, remote.clone()));
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let attempts_c = attempts.clone();
+    let downstream = Downstream::with_connector(move || {
+        if attempts_c.fetch_add(1, Ordering::SeqCst) == 0 {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                "not ready",
+            )));
+        }
+        TcpStream::connect(downstream_addr).map_err(Into::into)
+    });
+    let addr = common::serve_tcp(
+        Router::new(Host::new(1, Basic::new(1, 1, false))).with_route(5, downstream),
+    );
+    let mut client = common::connect(addr, 5);
+    // forwarding errors are replied as error frames
+    assert!(matches!(client.ping().unwrap_err().kind(), Error::Io(_)));
+    client.write_register(0, 0, &[1]).unwrap();
+    assert_eq!(remote.get_bytes(0, 0, 1).unwrap(), [1]);
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn unconfirmed_commands_are_not_waited() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let remote = Host::new(5, Basic::new(1, 1, false))
+        .with_custom_command_handler(Arc::new(Counter(calls.clone())));
+    let downstream_addr = common::serve_tcp(remote);
+    let router = Router::new(Host::new(1, Basic::new(1, 1, false)))
+        .with_route(
+            5,
+            Downstream::new(TcpStream::connect(downstream_addr).unwrap()),
+        )
+        .with_unconfirmed_command(Command::Other(0x8001));
+    let addr = common::serve_tcp(router);
+    let mut client = common::connect(addr, 5);
+    assert!(client
+        .communicate(Command::Other(0x8001), &[], false)
+        .unwrap()
+        .is_none());
+    // the downstream processes the frames in order
+    client.ping().unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn frames_keep_the_original_source() {
+    let remote = Host::new(5, Basic::new(1, 1, false))
+        .try_with_typed_command(0x8001, "source", |frame: &Frame, (): ()| Ok(frame.source))
+        .unwrap();
+    let downstream_addr = common::serve_tcp(remote);
+    let router = Router::new(Host::new(1, Basic::new(1, 1, false))).with_route(
+        5,
+        Downstream::new(TcpStream::connect(downstream_addr).unwrap()),
+    );
+    let addr = common::serve_tcp(router);
+    let mut client = common::connect(addr, 5).with_source_id(7);
+    assert_eq!(client.call::<_, u32>(0x8001, &()).unwrap(), 7);
+    let mut client = common::connect(addr, 5).with_source_id(8);
+    assert_eq!(client.call::<_, u32>(0x8001, &()).unwrap(), 8);
+}
+
+#[test]
+fn hung_downstream_times_out() {
+    // accepts connections but never replies
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let hung = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let _accepted = listener.accept().unwrap();
+    let local = Basic::new(1, 1, false);
+    let router = Router::new(Host::new(1, local.clone())).with_route(
+        5,
+        Downstream::new(hung).with_timeout(Duration::from_millis(100)),
+    );
+    let addr = common::serve_tcp(router);
+    let mut client = common::connect(addr, 5);
+    assert!(matches!(client.ping().unwrap_err().kind(), Error::Timeout));
+    assert!(matches!(client.ping().unwrap_err().kind(), Error::Timeout));
+    let mut client = common::connect(addr, 1);
+    client.write_register(0, 0, &[1]).unwrap();
+    assert_eq!(local.get_bytes(0, 0, 1).unwrap(), [1]);
+}
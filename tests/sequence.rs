@@ -0,0 +1,114 @@
+use std::io::Cursor;
+
+use binrw::prelude::*;
+use rpdo::comm::{Command, Frame, Hello, RawDataHeader};
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::{Host, SyncHost};
+
+fn frame(source: u32, id: u32, command: Command) -> Frame {
+    Frame {
+        source,
+        target: 1,
+        id,
+        in_reply_to: 0,
+        command,
+    }
+}
+
+fn write(host: &Host<Basic>, source: u32, id: u32, value: u8) {
+    let mut buf = Cursor::new(Vec::new());
+    RawDataHeader {
+        register: 0,
+        offset: 0,
+        size: 1,
+    }
+    .write(&mut buf)
+    .unwrap();
+    buf.get_mut().push(value);
+    let reply = host
+        .process_frame(
+            &frame(source, id, Command::WriteSharedContextUnconfirmed),
+            buf.get_ref(),
+        )
+        .unwrap();
+    assert!(reply.is_none());
+}
+
+fn value(context: &Basic) -> u8 {
+    context.get_bytes(0, 0, 1).unwrap()[0]
+}
+
+#[test]
+fn duplicate_and_stale_frames_are_dropped() {
+    let context = Basic::new(1, 1, false);
+    let host = Host::new(1, context.clone()).with_sequence_window(8);
+    write(&host, 5, 10, 1);
+    write(&host, 5, 10, 2);
+    write(&host, 5, 7, 3);
+    assert_eq!(value(&context), 1);
+    assert_eq!(host.dropped_frames(), 2);
+    // other sources are tracked separately
+    write(&host, 6, 1, 4);
+    assert_eq!(value(&context), 4);
+    // far behind, considered as a source restart
+    write(&host, 5, 1, 5);
+    assert_eq!(value(&context), 5);
+    assert_eq!(host.dropped_frames(), 2);
+}
+
+#[test]
+fn anonymous_frames_are_not_tracked() {
+    let context = Basic::new(1, 1, false);
+    let host = Host::new(1, context.clone()).with_sequence_window(8);
+    write(&host, 0, 10, 1);
+    write(&host, 0, 10, 2);
+    write(&host, 0, 9, 3);
+    assert_eq!(value(&context), 3);
+    assert_eq!(host.dropped_frames(), 0);
+}
+
+#[test]
+fn hello_resets_the_window() {
+    let context = Basic::new(1, 1, false);
+    let host = Host::new(1, context.clone()).with_sequence_window(8);
+    write(&host, 5, 10, 1);
+    let mut buf = Cursor::new(Vec::new());
+    Hello::new(0).write(&mut buf).unwrap();
+    let reply = host
+        .process_frame(&frame(5, 0, Command::Hello), buf.get_ref())
+        .unwrap()
+        .unwrap();
+    assert_eq!(reply.0.command, Command::Reply);
+    write(&host, 5, 1, 2);
+    assert_eq!(value(&context), 2);
+    assert_eq!(host.dropped_frames(), 0);
+}
+
+#[test]
+fn malformed_frames_do_not_move_the_window() {
+    let context = Basic::new(1, 1, false);
+    let host = Host::new(1, context.clone()).with_sequence_window(8);
+    write(&host, 5, 5, 1);
+    // truncated: the header declares more data than carried
+    let mut buf = Cursor::new(Vec::new());
+    RawDataHeader {
+        register: 0,
+        offset: 0,
+        size: 2,
+    }
+    .write(&mut buf)
+    .unwrap();
+    buf.get_mut().push(9);
+    assert!(host
+        .process_frame(
+            &frame(5, 10, Command::WriteSharedContextUnconfirmed),
+            buf.get_ref(),
+        )
+        .is_err());
+    assert!(host
+        .process_frame(&frame(5, 11, Command::WriteSharedContextUnconfirmed), &[1])
+        .is_err());
+    write(&host, 5, 6, 2);
+    assert_eq!(value(&context), 2);
+    assert_eq!(host.dropped_frames(), 0);
+}
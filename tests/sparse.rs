@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use std::thread;
+
+use rpdo::context::{RpdoContext, Sparse};
+use rpdo::Error;
+
+#[test]
+fn failed_writes_do_not_create_registers() {
+    let context = Sparse::new(4, false).with_auto_create(true);
+    let err = context.set_bytes(7, 2, &[1, 2, 3]).unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidOffset));
+    assert!(context.registers().is_empty());
+    assert_eq!(context.memory_usage(), 0);
+    context.set_bytes(7, 2, &[1, 2]).unwrap();
+    assert_eq!(context.registers(), [7]);
+    assert_eq!(context.get_bytes(7, 0, 0).unwrap(), [0, 0, 1, 2]);
+    assert_eq!(context.memory_usage(), 4);
+}
+
+#[test]
+fn memory_is_limited() {
+    let context = Sparse::new(4, true)
+        .with_auto_create(true)
+        .with_max_memory(10);
+    context.set_bytes(0, 0, &[1; 6]).unwrap();
+    assert_eq!(context.memory_usage(), 6);
+    assert!(matches!(
+        context.set_bytes(1, 0, &[1; 5]),
+        Err(Error::Overflow)
+    ));
+    assert!(matches!(
+        context.set_bytes(0, 4, &[1; 8]),
+        Err(Error::Overflow)
+    ));
+    context.set_bytes(1, 0, &[1; 4]).unwrap();
+    assert_eq!(context.memory_usage(), 10);
+    context.remove_register(0).unwrap();
+    assert_eq!(context.memory_usage(), 4);
+    assert!(context.get_bytes(0, 0, 0).is_err());
+}
+
+#[test]
+fn registers_are_limited() {
+    let context = Sparse::new(1, false)
+        .with_auto_create(true)
+        .with_max_registers(2);
+    context.create_register(0).unwrap();
+    context.set_bytes(1, 0, &[1]).unwrap();
+    assert!(matches!(
+        context.set_bytes(2, 0, &[1]),
+        Err(Error::Overflow)
+    ));
+    assert!(context.set_bytes(3, 0, &[1]).is_err());
+    assert_eq!(context.registers(), [0, 1]);
+}
+
+#[test]
+fn concurrent_writes() {
+    let context = Arc::new(Sparse::new(8, false).with_auto_create(true));
+    let threads: Vec<_> = (0..8u8)
+        .map(|i| {
+            let context = context.clone();
+            thread::spawn(move || {
+                for register in 0..16 {
+                    context.set_bytes(register, u32::from(i), &[i]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(context.memory_usage(), 16 * 8);
+    for register in 0..16 {
+        assert_eq!(
+            context.get_bytes(register, 0, 0).unwrap(),
+            [0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::io::Cursor;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use binrw::prelude::*;
+use rpdo::comm::{Command, Frame, Packet, RawDataHeader};
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::io::{UdpPublisher, UdpSubscriber};
+
+fn subscriber() -> (UdpSubscriber, std::net::SocketAddr) {
+    let subscriber = UdpSubscriber::create("127.0.0.1:0")
+        .unwrap()
+        .with_read_timeout(Duration::from_secs(5))
+        .unwrap();
+    let addr = subscriber.local_addr().unwrap();
+    (subscriber, addr)
+}
+
+fn write_frame(buf: &mut Vec<u8>, register: u32, data: &[u8]) {
+    let frame = Frame {
+        source: 1,
+        target: 0,
+        id: 0,
+        in_reply_to: 0,
+        command: Command::WriteSharedContextUnconfirmed,
+    };
+    Packet::new(frame, RawDataHeader::SIZE + data.len())
+        .write_to(buf)
+        .unwrap();
+    let mut c = Cursor::new(Vec::new());
+    RawDataHeader {
+        register,
+        offset: 0,
+        size: u32::try_from(data.len()).unwrap(),
+    }
+    .write(&mut c)
+    .unwrap();
+    buf.extend(c.into_inner());
+    buf.extend(data);
+}
+
+#[test]
+fn refused_writes_are_skipped() {
+    let (mut subscriber, addr) = subscriber();
+    let source = Basic::new(6, 2, false);
+    source.set_bytes(0, 0, &[1, 2]).unwrap();
+    source.set_bytes(5, 0, &[3, 4]).unwrap();
+    source.set_bytes(1, 0, &[5, 6]).unwrap();
+    let mut publisher = UdpPublisher::create("127.0.0.1:0", addr)
+        .unwrap()
+        .with_source_id(1)
+        .with_register(0, 0, 0)
+        .with_register(5, 0, 0)
+        .with_register(1, 0, 0);
+    publisher.publish(&source).unwrap();
+    let context = Basic::new(2, 2, false);
+    assert_eq!(subscriber.process_next(&context).unwrap(), 2);
+    assert_eq!(context.get_bytes(0, 0, 0).unwrap(), [1, 2]);
+    assert_eq!(context.get_bytes(1, 0, 0).unwrap(), [5, 6]);
+}
+
+#[test]
+fn malformed_datagram_is_not_applied() {
+    let (mut subscriber, addr) = subscriber();
+    let mut datagram = Vec::new();
+    write_frame(&mut datagram, 0, &[1, 2]);
+    write_frame(&mut datagram, 1, &[3, 4]);
+    // the last frame is truncated
+    datagram.truncate(datagram.len() - 1);
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.send_to(&datagram, addr).unwrap();
+    let context = Basic::new(2, 2, false);
+    assert!(subscriber.process_next(&context).is_err());
+    assert_eq!(context.get_bytes(0, 0, 0).unwrap(), [0, 0]);
+}
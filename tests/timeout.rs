@@ -0,0 +1,84 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+use binrw::prelude::*;
+use rpdo::comm::{Command, Frame, Packet, CAP_COMPRESSION};
+use rpdo::context::Basic;
+use rpdo::host::Host;
+
+const COMMAND_SLEEP: u16 = 0x8000;
+
+#[binrw]
+#[brw(little)]
+struct Sleep {
+    ms: u32,
+}
+
+fn sleeping_host() -> Host<Basic> {
+    Host::new(1, Basic::new(1, 4096, false))
+        .try_with_typed_command(COMMAND_SLEEP, "sleep", |_, request: Sleep| {
+            thread::sleep(Duration::from_millis(request.ms.into()));
+            Ok(Sleep { ms: request.ms })
+        })
+        .unwrap()
+}
+
+#[test]
+fn timed_out_reply_is_skipped_and_stream_timeout_restored() {
+    let addr = common::serve_tcp(sleeping_host());
+    let mut client = common::connect(addr, 1).with_timeout(Duration::from_millis(100));
+    let result: rpdo::Result<Sleep> = client.call(COMMAND_SLEEP, &Sleep { ms: 300 });
+    assert!(matches!(result, Err(rpdo::Error::Timeout)));
+    // the stream timeout (5 seconds) is used again, the late reply is skipped
+    client.set_timeout(None);
+    let reply: Sleep = client.call(COMMAND_SLEEP, &Sleep { ms: 200 }).unwrap();
+    assert_eq!(reply.ms, 200);
+}
+
+#[test]
+fn deadline_propagation_keeps_negotiated_session() {
+    let addr = common::serve_tcp(sleeping_host());
+    let mut client = common::connect(addr, 1)
+        .with_timeout(Duration::from_secs(2))
+        .with_deadline_propagation(true);
+    client.hello(CAP_COMPRESSION).unwrap();
+    let data = vec![0xAA; 4096];
+    client.write_register(0, 0, &data).unwrap();
+    assert_eq!(client.read_register(0, 0, 0).unwrap(), data);
+}
+
+#[test]
+fn timeout_inside_packet_desynchronizes_client() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 64];
+        let _ = stream.read(&mut buf);
+        // only a part of the reply is sent
+        let frame = Frame {
+            source: 1,
+            target: 0,
+            id: 0,
+            in_reply_to: 0,
+            command: Command::Reply,
+        };
+        let mut reply = Vec::new();
+        Packet::new(frame, 0).write_to(&mut reply).unwrap();
+        stream.write_all(&reply[..10]).unwrap();
+        thread::sleep(Duration::from_secs(2));
+    });
+    let mut client = common::connect(addr, 1).with_timeout(Duration::from_millis(200));
+    match client.ping() {
+        Err(rpdo::Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+        v => panic!("unexpected result: {:?}", v),
+    }
+    match client.ping() {
+        Err(rpdo::Error::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::NotConnected),
+        v => panic!("unexpected result: {:?}", v),
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use rpdo::comm::{Command, Frame};
+use rpdo::context::Basic;
+use rpdo::host::{Host, SyncHost};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn ping(host: &Host<Basic>) -> String {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let frame = Frame {
+        source: 5,
+        target: 1,
+        id: 7,
+        in_reply_to: 0,
+        command: Command::Ping,
+    };
+    tracing::subscriber::with_default(subscriber, || {
+        host.process_frame(&frame, &[]).unwrap();
+    });
+    let output = buffer.0.lock().unwrap().clone();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn frame_events_are_traced_within_spans() {
+    let host = Host::new(1, Basic::new(1, 1, false)).with_trace_level(tracing::Level::INFO);
+    let output = ping(&host);
+    assert!(
+        output.contains("frame{host=1 source=5 target=1 id=7"),
+        "{output}"
+    );
+    assert!(output.contains("reply"), "{output}");
+    // DEBUG by default
+    let output = ping(&Host::new(1, Basic::new(1, 1, false)));
+    assert!(output.is_empty(), "{output}");
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use rpdo::context::{Basic, Constraint, RpdoContext, Validated, ValueType};
+use rpdo::host::Host;
+use rpdo::Error;
+
+fn context() -> Validated<Basic> {
+    Validated::new(Basic::new(4, 8, false))
+        .with_constraint(
+            0..2,
+            Constraint::value(0, ValueType::I16)
+                .with_min(-10.0)
+                .with_max(10.0),
+        )
+        .unwrap()
+        .with_constraint(
+            3..=3,
+            Constraint::value(4, ValueType::F32).with_allowed([0.5, 1.5]),
+        )
+        .unwrap()
+}
+
+#[test]
+fn value_constraints() {
+    let context = context();
+    context.set_bytes(0, 0, &(-10i16).to_le_bytes()).unwrap();
+    assert!(context.set_bytes(1, 0, &11i16.to_le_bytes()).is_err());
+    // partial writes of a value
+    assert!(context.set_bytes(1, 1, &[0]).is_err());
+    // other offsets and registers are not constrained
+    context.set_bytes(1, 2, &[0xff; 6]).unwrap();
+    context.set_bytes(2, 0, &[0xff; 8]).unwrap();
+    context.set_bytes(3, 4, &1.5f32.to_le_bytes()).unwrap();
+    assert!(context.set_bytes(3, 4, &2.5f32.to_le_bytes()).is_err());
+    assert!(context.set_bytes(3, 4, &f32::NAN.to_le_bytes()).is_err());
+    assert_eq!(context.get_bytes(3, 4, 4).unwrap(), 1.5f32.to_le_bytes());
+}
+
+#[test]
+fn remote_writes_are_validated() {
+    let context = context();
+    let addr = common::serve_tcp(Host::new(1, context.clone()));
+    let mut client = common::connect(addr, 1);
+    let err = client
+        .write_register(0, 0, &20i16.to_le_bytes())
+        .unwrap_err();
+    assert!(matches!(err.kind(), Error::Validation(_)));
+    client.write_register(0, 0, &5i16.to_le_bytes()).unwrap();
+    assert_eq!(context.get_bytes(0, 0, 2).unwrap(), 5i16.to_le_bytes());
+}
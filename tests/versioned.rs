@@ -0,0 +1,114 @@
+mod common;
+
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use binrw::prelude::*;
+use rpdo::comm::{Command, RawDataHeader};
+use rpdo::context::{Basic, RpdoContext, Versioned};
+use rpdo::host::Host;
+use rpdo::Error;
+
+/// Blocks writes of the register 0 while the gate is locked
+#[derive(Clone)]
+struct Gated {
+    context: Basic,
+    gate: Arc<Mutex<()>>,
+    entered: Arc<AtomicBool>,
+}
+
+impl RpdoContext for Gated {
+    fn get_bytes(&self, register: u32, offset: u32, data_size: u32) -> rpdo::Result<Vec<u8>> {
+        self.context.get_bytes(register, offset, data_size)
+    }
+    fn set_bytes(&self, register: u32, offset: u32, data: &[u8]) -> rpdo::Result<()> {
+        let _gate = (register == 0).then(|| {
+            self.entered.store(true, Ordering::SeqCst);
+            self.gate.lock().unwrap()
+        });
+        self.context.set_bytes(register, offset, data)
+    }
+}
+
+#[test]
+fn metadata_tracks_writers() {
+    let context = Versioned::new(Basic::new(2, 4, false));
+    context.set_bytes(0, 0, &[1, 1, 1, 1]).unwrap();
+    assert_eq!(context.metadata(0).source, 0);
+    assert_eq!(context.version(0), 1);
+    let addr = common::serve_tcp(Host::new(1, context.clone()));
+    let mut client = common::connect(addr, 1).with_source_id(9);
+    client.write_register(0, 0, &[2, 2, 2, 2]).unwrap();
+    let metadata = context.metadata(0);
+    assert_eq!((metadata.version, metadata.source), (2, 9));
+    let (metadata, data) = client.read_register_if_changed(0, 0, 0, 1).unwrap();
+    assert_eq!(metadata.version, 2);
+    assert_eq!(data.unwrap(), [2, 2, 2, 2]);
+    let (_, data) = client.read_register_if_changed(0, 0, 0, 2).unwrap();
+    assert!(data.is_none());
+    // failed writes do not change the metadata
+    assert!(client.write_register(1, 2, &[3, 3, 3, 3]).is_err());
+    assert!(client.write_register(5, 0, &[3]).is_err());
+    assert_eq!(context.version(1), 0);
+    assert_eq!(context.version(5), 0);
+}
+
+#[test]
+fn registers_are_locked_separately() {
+    let gate = Arc::new(Mutex::new(()));
+    let entered = Arc::new(AtomicBool::new(false));
+    let context = Versioned::new(Gated {
+        context: Basic::new(2, 1, false),
+        gate: gate.clone(),
+        entered: entered.clone(),
+    });
+    let locked = gate.lock().unwrap();
+    let blocked = context.clone();
+    let writer = thread::spawn(move || blocked.set_bytes(0, 0, &[1]).unwrap());
+    // wait until the writer holds the register 0
+    while !entered.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let (tx, rx) = mpsc::channel();
+    let free = context.clone();
+    thread::spawn(move || {
+        free.set_bytes(1, 0, &[2]).unwrap();
+        tx.send(free.get_bytes_with_metadata(1, 0, 1, 0).unwrap())
+            .unwrap();
+    });
+    let (metadata, data) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(metadata.version, 1);
+    assert_eq!(data.unwrap(), [2]);
+    drop(locked);
+    writer.join().unwrap();
+    assert_eq!(context.version(0), 1);
+}
+
+#[test]
+fn communicate_returns_error_replies_as_data() {
+    let addr = common::serve_tcp(Host::new(1, Basic::new(1, 4, false)));
+    let mut client = common::connect(addr, 1);
+    let mut buf = Cursor::new(Vec::new());
+    RawDataHeader {
+        register: 7,
+        offset: 0,
+        size: 0,
+    }
+    .write(&mut buf)
+    .unwrap();
+    let reply = client
+        .communicate(Command::ReadSharedContext, buf.get_ref(), true)
+        .unwrap()
+        .unwrap();
+    assert!(matches!(
+        Error::from(reply.as_slice()).kind(),
+        Error::InvalidRegister
+    ));
+    let err = client
+        .request(Command::ReadSharedContext, buf.get_ref(), true)
+        .unwrap_err();
+    assert!(matches!(err.kind(), Error::InvalidRegister));
+}
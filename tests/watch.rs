@@ -0,0 +1,64 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use rpdo::context::{Basic, RpdoContext, Watched, WriteEvent};
+use rpdo::host::Host;
+
+#[test]
+fn writes_are_notified() {
+    let context = Watched::new(Basic::new(2, 4, false));
+    let all = context.watch(8, false);
+    let changes = context.watch(8, true);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_c = events.clone();
+    context.on_write(false, move |event| events_c.lock().unwrap().push(event));
+    context.set_bytes(0, 1, &[1, 2]).unwrap();
+    // the same value again is not a change
+    context.set_bytes(0, 1, &[1, 2]).unwrap();
+    // failed writes are not notified
+    assert!(context.set_bytes(5, 0, &[1]).is_err());
+    let event = WriteEvent {
+        register: 0,
+        offset: 1,
+        len: 2,
+    };
+    assert_eq!(all.try_recv().unwrap(), event);
+    assert_eq!(all.try_recv().unwrap(), event);
+    assert!(all.try_recv().is_err());
+    assert_eq!(changes.try_recv().unwrap(), event);
+    assert!(changes.try_recv().is_err());
+    assert_eq!(*events.lock().unwrap(), [event, event]);
+}
+
+#[test]
+fn remote_writes_are_notified() {
+    let context = Watched::new(Basic::new(2, 4, false));
+    let rx = context.watch(8, true);
+    let addr = common::serve_tcp(Host::new(1, context.clone()));
+    let mut client = common::connect(addr, 1);
+    client.write_register(1, 0, &[1, 2, 3, 4]).unwrap();
+    assert_eq!(
+        rx.try_recv().unwrap(),
+        WriteEvent {
+            register: 1,
+            offset: 0,
+            len: 4,
+        }
+    );
+}
+
+#[test]
+fn dropped_receivers_are_removed() {
+    let context = Watched::new(Basic::new(1, 1, false));
+    let rx = context.watch(1, false);
+    drop(rx);
+    context.set_bytes(0, 0, &[1]).unwrap();
+    let rx = context.watch(1, false);
+    context.set_bytes(0, 0, &[2]).unwrap();
+    // a full channel drops events but the write succeeds
+    context.set_bytes(0, 0, &[3]).unwrap();
+    assert_eq!(rx.try_recv().unwrap().register, 0);
+    assert!(rx.try_recv().is_err());
+    assert_eq!(context.get_bytes(0, 0, 1).unwrap(), [3]);
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rpdo::comm::Heartbeat;
+use rpdo::context::{Basic, RpdoContext};
+use rpdo::host::Host;
+use rpdo::watchdog::{Watchdog, WatchdogEvent};
+
+#[test]
+fn heartbeat_interval_is_rounded_up() {
+    assert_eq!(Heartbeat::new(Duration::ZERO).interval_ms, 0);
+    assert_eq!(Heartbeat::new(Duration::from_micros(1)).interval_ms, 1);
+    assert_eq!(Heartbeat::new(Duration::from_micros(1500)).interval_ms, 2);
+    assert_eq!(Heartbeat::new(Duration::from_millis(3)).interval_ms, 3);
+}
+
+#[test]
+fn lapsed_heartbeat_runs_safe_state_actions() {
+    let context = Basic::new(1, 1, false);
+    context.set_bytes(0, 0, &[1]).unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_c = events.clone();
+    let watchdog = Watchdog::new()
+        .with_safe_value(context.clone(), 0, 0, &[0])
+        .with_event_handler(move |event| events_c.lock().unwrap().push(event));
+    let host = Host::new(1, context.clone()).with_watchdog(watchdog.clone());
+    let addr = common::serve_tcp(host);
+
+    let mut anonymous = common::connect(addr, 1);
+    assert!(anonymous.heartbeat(Duration::from_secs(1)).is_err());
+
+    let mut client = common::connect(addr, 1).with_source_id(7);
+    // a sub-millisecond interval must not disarm the watchdog
+    client.heartbeat(Duration::from_micros(300)).unwrap();
+    assert_eq!(watchdog.is_expired(7), Some(false));
+    thread::sleep(Duration::from_millis(5));
+    watchdog.check();
+    assert_eq!(watchdog.is_expired(7), Some(true));
+    assert_eq!(context.get_bytes(0, 0, 1).unwrap(), [0]);
+    client.heartbeat(Duration::ZERO).unwrap();
+    assert_eq!(watchdog.is_expired(7), None);
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            WatchdogEvent::Connected {
+                source: 7,
+                interval: Duration::from_millis(1)
+            },
+            WatchdogEvent::Expired { source: 7 },
+            WatchdogEvent::Disarmed { source: 7 },
+        ]
+    );
+}